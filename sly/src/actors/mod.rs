@@ -1,5 +1,6 @@
 use actix::{Actor, Addr};
 use anyhow::Result;
+use std::path::Path;
 
 use replica::{ReplicaActor, ReplicaActorConfig};
 use shutdown_controller::ShutdownController;
@@ -17,16 +18,17 @@ pub fn start_shutdown_controller() -> Result<Addr<ShutdownController>> {
     Ok(ShutdownController::default().start())
 }
 
-/// Start a replica, returns the actor's address.
+/// Start a replica that stores its data in the given root, returns the actor's address.
 pub fn start_replica(
     shutdown_controller: Option<Addr<ShutdownController>>,
     no_artificial_delay: bool,
+    root: &Path,
 ) -> Result<Addr<ReplicaActor>> {
     let replica_path = toolchain::get_binary_command_path("replica")?;
     let ic_starter_path = toolchain::get_binary_command_path("ic-starter")?;
-    let state_directory = toolchain::get_replica_state_directory()?;
-    let write_port_to = toolchain::get_replica_port_file()?;
-    let write_pid_to = Some(toolchain::get_replica_pid_file()?);
+    let state_directory = toolchain::get_replica_state_directory(root)?;
+    let write_port_to = toolchain::get_replica_port_file(root);
+    let write_pid_to = Some(toolchain::get_replica_pid_file(root));
 
     let config = ReplicaActorConfig {
         ic_starter_path,
@@ -1,8 +1,17 @@
+use crate::lib::build_cache::{BuildCache, BuildInputs};
 use crate::lib::command::Command;
 use crate::lib::env::Env;
+use crate::lib::workspace::{CanisterKind, OptimizeSettings, Workspace};
+use crate::lib::{dfx, rust, shell, wasm};
+use crate::wasm_optimizer;
 use anyhow::{anyhow, bail, Context};
 use clap::Parser as Clap;
-use std::process::Command as CommandExec;
+use crossbeam::channel::unbounded;
+use std::collections::BTreeMap;
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
 
 #[derive(Clap)]
 pub struct BuildOpts {
@@ -12,6 +21,12 @@ pub struct BuildOpts {
     /// Build the code for all of the canisters in sly.json.
     #[clap(long)]
     pub all: bool,
+    /// The number of canisters to build in parallel, defaults to the number of CPUs.
+    #[clap(short, long)]
+    pub jobs: Option<usize>,
+    /// Run the build commands even if the inputs of a canister did not change.
+    #[clap(long)]
+    pub no_cache: bool,
     /// The canister to build.
     pub canisters: Vec<String>,
 }
@@ -22,14 +37,23 @@ impl Command for BuildOpts {
             bail!("Unexpect canisters list when --all is provided.");
         }
 
+        if self.jobs == Some(0) {
+            bail!("The number of jobs must be at least 1.");
+        }
+
         let workspace = env.workspace()?;
 
-        let canisters = if self.all {
+        let canisters: Vec<String> = if self.all {
             workspace.canisters.keys().cloned().collect()
         } else {
             self.canisters.clone()
         };
 
+        let canisters =
+            workspace.filter_deployable(canisters, &env.network()?, &env.network_config()?);
+
+        let levels = workspace.topological_levels(&canisters)?;
+
         for name in &canisters {
             let build = &workspace
                 .get_canister(name)
@@ -43,24 +67,191 @@ impl Command for BuildOpts {
                 .ok_or_else(|| anyhow!("Canister '{}' does not have a build command for mode '{}'. Use --with-mode=[{}]", name, self.with_mode, modes.join("/")))?;
         }
 
-        for name in canisters {
-            let commands = workspace
-                .get_canister(&name)
-                .unwrap()
-                .build
-                .get(&self.with_mode)
-                .unwrap();
-
-            for command in commands {
-                // TODO: Shell Expand
-                CommandExec::new("sh")
-                    .arg("-c")
-                    .arg(command)
-                    .spawn()
-                    .with_context(|| format!("Could not execute command '{}'", command))?;
+        let has_rust_canister = canisters.iter().any(|name| {
+            matches!(
+                workspace.get_canister(name).unwrap().kind,
+                CanisterKind::Rust { .. }
+            )
+        });
+
+        if has_rust_canister {
+            rust::check_wasm_target()?;
+        }
+
+        let canister_ids = env.canister_ids()?;
+        let mut envs = BTreeMap::new();
+
+        for name in &canisters {
+            // The id of the dependencies are usually compiled in, so they must exist.
+            for dep in &workspace.get_canister(name).unwrap().dependencies {
+                if !canister_ids.contains_key(dep) {
+                    bail!(
+                        "Canister '{}' depends on '{}' which is not created yet.",
+                        name,
+                        dep
+                    );
+                }
             }
+
+            envs.insert(name.clone(), env.command_envs(name, &self.with_mode)?);
+        }
+
+        let jobs = self.jobs.unwrap_or_else(num_cpus::get);
+
+        // Canisters in the same level do not depend on each other, so they can be built in
+        // parallel, but a level must be done before we start the next one.
+        // The ids of the canisters are compiled in, so the builds are cached per network.
+        let cache = BuildCache::new(&workspace.sly_dir(), &env.network()?);
+
+        for level in levels {
+            self.build_level(&workspace, &cache, &envs, level, jobs)?;
         }
 
         Ok(())
     }
 }
+
+impl BuildOpts {
+    /// Build the given canisters using at most `jobs` threads. Once a build fails no new
+    /// build is started, and the error of the first failure is returned.
+    fn build_level(
+        &self,
+        workspace: &Workspace,
+        cache: &BuildCache,
+        envs: &BTreeMap<String, Vec<(String, String)>>,
+        level: Vec<String>,
+        jobs: usize,
+    ) -> anyhow::Result<()> {
+        let workers = jobs.min(level.len());
+        let (sender, receiver) = unbounded();
+        for name in level {
+            sender.send(name).unwrap();
+        }
+        drop(sender);
+
+        let failed = AtomicBool::new(false);
+        let errors = Mutex::new(Vec::new());
+
+        crossbeam::scope(|s| {
+            for _ in 0..workers {
+                s.spawn(|_| {
+                    for name in receiver.iter() {
+                        if failed.load(Ordering::SeqCst) {
+                            break;
+                        }
+
+                        if let Err(e) = self.build_canister(workspace, cache, &envs[&name], &name) {
+                            failed.store(true, Ordering::SeqCst);
+                            errors.lock().unwrap().push(e);
+                        }
+                    }
+                });
+            }
+        })
+        .map_err(|_| anyhow!("A build thread panicked."))?;
+
+        match errors.into_inner().unwrap().into_iter().next() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Run the build commands of a canister one after the other.
+    fn build_canister(
+        &self,
+        workspace: &Workspace,
+        cache: &BuildCache,
+        envs: &[(String, String)],
+        name: &str,
+    ) -> anyhow::Result<()> {
+        let canister = workspace.get_canister(name).unwrap();
+        let commands = canister.build.get(&self.with_mode).unwrap();
+
+        let output = workspace.get_wasm_path(name, &self.with_mode);
+        let key = if self.no_cache {
+            None
+        } else {
+            let toolchain = toolchain_version(&canister.kind);
+            BuildCache::compute_key(&BuildInputs {
+                root: &workspace.root,
+                globs: &canister.inputs,
+                commands,
+                envs,
+                dependencies: &canister.dependencies,
+                toolchain: &toolchain,
+                optimize: canister.optimize_settings(&self.with_mode),
+            })?
+        };
+
+        if let (Some(key), Some(output)) = (&key, &output) {
+            if cache.is_fresh(name, &self.with_mode, key, output) {
+                println!("Canister '{}' is up to date.", name);
+                return Ok(());
+            }
+        }
+
+        let prefix = format!("[{}]", name);
+        let start = Instant::now();
+
+        for command in commands {
+            shell::run_prefixed(command, &workspace.root, envs.iter().cloned(), &prefix)
+                .with_context(|| format!("Failed to build canister '{}'.", name))?;
+        }
+
+        if let Some(settings) = canister.optimize_settings(&self.with_mode) {
+            self.optimize_canister(workspace, name, settings)?;
+        }
+
+        if let (Some(key), Some(output)) = (&key, &output) {
+            if output.is_file() {
+                cache.store(name, &self.with_mode, key, output)?;
+            }
+        }
+
+        println!("Built '{}' in {:.2}s", name, start.elapsed().as_secs_f64());
+
+        Ok(())
+    }
+
+    /// Run the optimization passes on the wasm of a canister that was just built.
+    fn optimize_canister(
+        &self,
+        workspace: &Workspace,
+        name: &str,
+        settings: &OptimizeSettings,
+    ) -> anyhow::Result<()> {
+        let canister = workspace.get_canister(name).unwrap();
+        let input = match canister.wasm.get(&self.with_mode) {
+            Some(path) => workspace.root.join(path),
+            None => return Ok(()),
+        };
+
+        if wasm::is_gzip_path(&input) {
+            log::debug!("Not optimizing the gzipped wasm of '{}'.", name);
+            return Ok(());
+        }
+
+        let output = wasm::optimized_path(&input);
+        log::info!("Optimizing the wasm of '{}'", name);
+
+        let module = fs::read(&input).with_context(|| format!("Could not read {:?}", input))?;
+        let passes = wasm_optimizer::create_from_names(settings.passes.as_deref(), settings.level)?;
+        let module = wasm_optimizer::run(passes, &module)
+            .with_context(|| format!("Failed to optimize the wasm of '{}'.", name))?;
+        fs::write(&output, module).with_context(|| format!("Could not write {:?}", output))?;
+
+        Ok(())
+    }
+}
+
+/// Return the version of the toolchain that is used to build a canister of the given kind.
+fn toolchain_version(kind: &CanisterKind) -> String {
+    let version = match kind {
+        CanisterKind::Rust { .. } => rust::compiler_version(),
+        _ => dfx::get_dfx_bin_root()
+            .ok()
+            .and_then(|root| root.file_name().map(|n| n.to_string_lossy().to_string())),
+    };
+
+    version.unwrap_or_default()
+}
@@ -1,15 +1,42 @@
 use anyhow::{bail, Context, Result};
 use candid::parser::value::IDLValue;
 use candid::types::{Function, Type};
-use candid::{IDLArgs, TypeEnv};
+use candid::{IDLArgs, Principal, TypeEnv};
 use clap::Parser as Clap;
 use codespan_reporting::term;
 use codespan_reporting::term::termcolor::{ColorChoice, StandardStream};
 use ic_agent::{agent::agent_error::HttpErrorPayload, AgentError};
 
 use crate::lib::candid::CandidParser;
+use crate::lib::env::Env;
 use crate::lib::utils;
 
+/// Resolve the canister id and the path to the candid file that should be used for a call,
+/// the canister can be referenced either by its name in sly.json or by its principal id.
+pub fn resolve_call_target(
+    env: &Env,
+    canister: &str,
+    candid: &Option<String>,
+    mode: &str,
+) -> Result<(Principal, Option<String>)> {
+    let canister_id = env.resolve_canister_id(canister)?;
+
+    if candid.is_some() {
+        return Ok((canister_id, candid.clone()));
+    }
+
+    let candid = env
+        .try_workspace()?
+        .and_then(|workspace| workspace.get_candid_path(canister, mode))
+        .map(|path| path.to_string_lossy().to_string());
+
+    if let Some(path) = &candid {
+        log::trace!("Using candid file {} for canister '{}'", path, canister);
+    }
+
+    Ok((canister_id, candid))
+}
+
 pub fn get_candid_type(idl_path: &str, method_name: &str) -> Result<Option<(TypeEnv, Function)>> {
     let (env, ty) = check_candid_file(idl_path)
         .with_context(|| format!("Failed when checking candid file: {}", idl_path))?;
@@ -12,9 +12,13 @@ pub mod waiter;
 
 #[derive(Clap)]
 pub struct CallSubCommands {
-    /// Path to a candid file to analyze the argument
+    /// Path to a candid file to analyze the argument, defaults to the candid file of
+    /// the canister in sly.json.
     #[clap(long, short)]
     candid: Option<String>,
+    /// For conditional sly.json evaluation.
+    #[clap(long, default_value = "default")]
+    with_mode: String,
     /// The type of input (raw or idl).
     #[clap(long, short, possible_values = & (["raw", "idl"]), default_value = "idl")]
     in_type: helper::ArgType,
@@ -39,7 +43,7 @@ enum Cmd {
 
 #[derive(Clap)]
 pub struct Args {
-    /// Canister id
+    /// Name of the canister in sly.json or its principal id
     canister_id: String,
     /// Method name to call on the canister
     method_name: String,
@@ -2,7 +2,6 @@ use std::ops::Deref;
 use std::time::Duration;
 
 use anyhow::{Context, Result};
-use candid::Principal;
 
 use crate::commands::call::waiter;
 use crate::commands::call::{helper, Args, CallSubCommands};
@@ -10,12 +9,13 @@ use crate::lib::env::Env;
 use crate::lib::utils;
 
 pub async fn async_exec(args: &Args, opts: &CallSubCommands, env: &Env) -> Result<()> {
-    let canister_id = &Principal::from_text(args.canister_id.deref())
-        .context("Invalid canister principal format")?;
+    let (canister_id, candid) =
+        helper::resolve_call_target(env, &args.canister_id, &opts.candid, &opts.with_mode)?;
+    let canister_id = &canister_id;
 
     let method_name = args.method_name.deref();
 
-    let method_type = match &opts.candid {
+    let method_type = match &candid {
         None => None,
         Some(path) => helper::get_candid_type(path, method_name)
             .context("Failed to get method type from candid file")?,
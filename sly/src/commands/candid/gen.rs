@@ -1,7 +1,7 @@
 use std::fs;
 use std::path::{Path, PathBuf};
 
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
 use clap::Parser as Clap;
 use codespan_reporting::term;
 use codespan_reporting::term::termcolor::{ColorChoice, StandardStream};
@@ -13,7 +13,7 @@ use crate::lib::utils::result_flatten;
 
 #[derive(Clap)]
 pub struct CandidGenOpts {
-    /// Path to the candid files.
+    /// Path to the candid files, or names of canisters in sly.json.
     files: Vec<String>,
     #[clap(short, long)]
     /// Directory
@@ -30,12 +30,13 @@ pub struct CandidGenOpts {
 }
 
 impl Command for CandidGenOpts {
-    fn exec(self, _: &mut Env) -> Result<()> {
+    fn exec(self, env: &mut Env) -> Result<()> {
         let dir = PathBuf::from(&self.out_dir);
 
         mkdirp::mkdirp(&dir)?;
 
         for file in &self.files {
+            let file = resolve_candid_file(env, file)?;
             gen_file_bindings(file.as_str(), &dir, &self)?;
         }
 
@@ -43,6 +44,22 @@ impl Command for CandidGenOpts {
     }
 }
 
+/// Return the path to the candid file, names of the canisters in the workspace are resolved
+/// to their candid file.
+fn resolve_candid_file(env: &Env, file: &str) -> Result<String> {
+    if Path::new(file).is_file() {
+        return Ok(file.to_owned());
+    }
+
+    match env.workspace() {
+        Ok(workspace) if workspace.get_canister(file).is_some() => workspace
+            .get_candid_path(file, "default")
+            .map(|path| path.to_string_lossy().to_string())
+            .ok_or_else(|| anyhow!("Canister '{}' does not have a candid file.", file)),
+        _ => Ok(file.to_owned()),
+    }
+}
+
 fn gen_file_bindings(file: &str, dir: &Path, opts: &CandidGenOpts) -> Result<()> {
     let mut parser = CandidParser::default();
     let maybe_env = result_flatten(parser.parse(file).map(|_| parser.construct_type_env()));
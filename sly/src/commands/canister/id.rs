@@ -0,0 +1,28 @@
+use anyhow::{anyhow, Result};
+use clap::Parser as Clap;
+
+use crate::lib::command::Command;
+use crate::lib::env::Env;
+
+#[derive(Clap)]
+pub struct CanisterIdOpts {
+    /// Name of the canister.
+    name: String,
+}
+
+impl Command for CanisterIdOpts {
+    fn exec(self, env: &mut Env) -> Result<()> {
+        let network = env.network()?;
+        let canister_id = env.canister_ids()?.remove(&self.name).ok_or_else(|| {
+            anyhow!(
+                "Canister '{}' does not have an id on network '{}'.",
+                self.name,
+                network
+            )
+        })?;
+
+        println!("{}", canister_id);
+
+        Ok(())
+    }
+}
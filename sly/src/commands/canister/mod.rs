@@ -0,0 +1,30 @@
+use anyhow::Result;
+use clap::Parser as Clap;
+
+use crate::lib::command::Command;
+use crate::lib::env::Env;
+
+pub mod id;
+pub mod set_id;
+pub mod sync_controllers;
+
+#[derive(Clap)]
+pub enum CanisterSubCommands {
+    /// Print the id of a canister on the current network.
+    Id(id::CanisterIdOpts),
+    /// Set the id of a canister on the current network, used to import canisters that
+    /// were created elsewhere.
+    SetId(set_id::CanisterSetIdOpts),
+    /// Update the controllers of already created canisters to match sly.json.
+    SyncControllers(sync_controllers::CanisterSyncControllersOpts),
+}
+
+impl Command for CanisterSubCommands {
+    fn exec(self, env: &mut Env) -> Result<()> {
+        match self {
+            CanisterSubCommands::Id(opts) => opts.exec(env),
+            CanisterSubCommands::SetId(opts) => opts.exec(env),
+            CanisterSubCommands::SyncControllers(opts) => opts.exec(env),
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use anyhow::{anyhow, Context, Result};
+use candid::Principal;
+use clap::Parser as Clap;
+
+use crate::lib::command::Command;
+use crate::lib::env::Env;
+
+#[derive(Clap)]
+pub struct CanisterSetIdOpts {
+    /// Name of the canister in sly.json.
+    name: String,
+    /// The id of the canister on the current network.
+    principal: String,
+}
+
+impl Command for CanisterSetIdOpts {
+    fn exec(self, env: &mut Env) -> Result<()> {
+        let canister_id =
+            Principal::from_text(&self.principal).context("Invalid canister principal format")?;
+
+        env.workspace()?
+            .get_canister(&self.name)
+            .ok_or_else(|| anyhow!("Canister '{}' not found.", self.name))?;
+
+        env.canister_id_store()?.set(&self.name, canister_id)?;
+
+        log::info!(
+            "Canister '{}' is set to {} on network '{}'.",
+            self.name,
+            canister_id,
+            env.network()?
+        );
+
+        Ok(())
+    }
+}
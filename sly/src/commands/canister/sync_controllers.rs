@@ -0,0 +1,125 @@
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use candid::Principal;
+use clap::Parser as Clap;
+use ic_agent::Agent;
+use ic_utils::call::AsyncCall;
+use ic_utils::interfaces::ManagementCanister;
+use std::collections::BTreeSet;
+
+use crate::commands::call::waiter;
+use crate::lib::command::AsyncCommand;
+use crate::lib::env::Env;
+
+#[derive(Clap)]
+pub struct CanisterSyncControllersOpts {
+    /// Sync the controllers of all the canisters in sly.json.
+    #[clap(long)]
+    all: bool,
+    /// Update the controllers even if neither the current identity nor the wallet is one of them.
+    #[clap(long)]
+    force: bool,
+    /// The canisters to sync.
+    canisters: Vec<String>,
+}
+
+#[async_trait]
+impl AsyncCommand for CanisterSyncControllersOpts {
+    async fn async_exec(self, env: &mut Env) -> Result<()> {
+        if !self.canisters.is_empty() && self.all {
+            bail!("Unexpect canisters list when --all is provided.");
+        }
+
+        let workspace = env.workspace()?;
+        let canister_ids = env.canister_ids()?;
+
+        let canisters: Vec<String> = if self.all {
+            workspace.canisters.keys().cloned().collect()
+        } else {
+            self.canisters.clone()
+        };
+
+        let canisters =
+            workspace.filter_deployable(canisters, &env.network()?, &env.network_config()?);
+        let agent = env.create_agent().await?;
+
+        for name in canisters {
+            let controllers = match env.canister_controllers(&name)? {
+                Some(controllers) => controllers,
+                None => {
+                    log::debug!("Canister '{}' does not specify any controllers.", name);
+                    continue;
+                }
+            };
+
+            // The management canister ignores an empty list instead of removing every controller.
+            if controllers.is_empty() {
+                bail!(
+                    "The list of controllers of '{}' in sly.json is empty.",
+                    name
+                );
+            }
+
+            if !self.force {
+                if let Err(e) = env.ensure_caller_is_controller(&name, &controllers) {
+                    bail!("{} Use --force to update the controllers anyway.", e);
+                }
+            }
+
+            let canister_id = canister_ids.get(&name).cloned().ok_or_else(|| {
+                anyhow!(
+                    "Canister '{}' is not created. Please use sly create first.",
+                    name
+                )
+            })?;
+
+            if sync_controllers(&agent, canister_id, controllers)
+                .await
+                .with_context(|| format!("Failed to sync the controllers of '{}'.", name))?
+            {
+                println!("Updated the controllers of '{}'.", name);
+            } else {
+                println!("The controllers of '{}' are up to date.", name);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Set the controllers of the canister if they are different from the given list, returns
+/// whether the settings were updated.
+async fn sync_controllers(
+    agent: &Agent,
+    canister_id: Principal,
+    controllers: Vec<Principal>,
+) -> Result<bool> {
+    let management = ManagementCanister::create(agent);
+
+    let (status,) = management
+        .canister_status(&canister_id)
+        .call_and_wait(waiter::waiter_with_exponential_backoff())
+        .await
+        .context("canister_status call failed.")?;
+
+    let current = status.settings.controllers.iter().collect::<BTreeSet<_>>();
+    let expected = controllers.iter().collect::<BTreeSet<_>>();
+
+    if current == expected {
+        return Ok(false);
+    }
+
+    let mut builder = management.update_settings(&canister_id);
+    for controller in controllers {
+        builder = builder.with_controller(controller);
+    }
+
+    builder
+        .build()
+        .context("Failed to build the update_settings call.")?
+        .call_and_wait(waiter::waiter_with_exponential_backoff())
+        .await
+        .context("update_settings call failed.")?;
+
+    Ok(true)
+}
@@ -0,0 +1,55 @@
+use anyhow::{Context, Result};
+use clap::Parser as Clap;
+use std::fs;
+use std::path::Path;
+
+use crate::lib::build_cache::BuildCache;
+use crate::lib::command::Command;
+use crate::lib::env::Env;
+use crate::lib::wasm;
+use crate::lib::workspace::CanisterKind;
+
+#[derive(Clap)]
+pub struct CleanOpts {}
+
+impl Command for CleanOpts {
+    fn exec(self, env: &mut Env) -> Result<()> {
+        let workspace = env.workspace()?;
+        let cache = BuildCache::new(&workspace.sly_dir(), &env.network()?);
+
+        log::info!("Removing the build cache {:?}", cache.dir());
+        cache.clear()?;
+
+        let build_dir = workspace.sly_dir().join("build");
+        if build_dir.is_dir() {
+            log::info!("Removing {:?}", build_dir);
+            fs::remove_dir_all(&build_dir)
+                .with_context(|| format!("Could not remove {:?}", build_dir))?;
+        }
+
+        // Only remove the wasm files that sly builds, a canister without build commands may
+        // point to a wasm that is checked in, and assets canisters use the toolchain's wasm.
+        for canister in workspace.canisters.values() {
+            let is_built = canister.build.values().any(|commands| !commands.is_empty());
+            if !is_built || matches!(canister.kind, CanisterKind::Assets { .. }) {
+                continue;
+            }
+
+            for path in canister.wasm.values() {
+                let path = workspace.root.join(path);
+                remove_file(&path)?;
+                remove_file(&wasm::optimized_path(&path))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn remove_file(path: &Path) -> Result<()> {
+    if path.is_file() {
+        log::info!("Removing {:?}", path);
+        fs::remove_file(path).with_context(|| format!("Could not remove {:?}", path))?;
+    }
+    Ok(())
+}
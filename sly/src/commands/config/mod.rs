@@ -0,0 +1,30 @@
+use anyhow::Result;
+use clap::Parser as Clap;
+
+use crate::lib::command::Command;
+use crate::lib::env::Env;
+
+pub mod schema;
+pub mod show;
+pub mod validate;
+
+#[derive(Clap)]
+pub enum ConfigSubCommands {
+    /// Check the sly.json of the current workspace for errors.
+    Validate(validate::ConfigValidateOpts),
+    /// Print the JSON schema of sly.json.
+    Schema(schema::ConfigSchemaOpts),
+    /// Print sly.json after merging the extended manifests and the selected profile, the
+    /// variables are not interpolated.
+    Show(show::ConfigShowOpts),
+}
+
+impl Command for ConfigSubCommands {
+    fn exec(self, env: &mut Env) -> Result<()> {
+        match self {
+            ConfigSubCommands::Validate(opts) => opts.exec(env),
+            ConfigSubCommands::Schema(opts) => opts.exec(env),
+            ConfigSubCommands::Show(opts) => opts.exec(env),
+        }
+    }
+}
@@ -0,0 +1,16 @@
+use anyhow::Result;
+use clap::Parser as Clap;
+
+use crate::lib::command::Command;
+use crate::lib::env::Env;
+use crate::lib::workspace::MANIFEST_SCHEMA;
+
+#[derive(Clap)]
+pub struct ConfigSchemaOpts {}
+
+impl Command for ConfigSchemaOpts {
+    fn exec(self, _: &mut Env) -> Result<()> {
+        print!("{}", MANIFEST_SCHEMA);
+        Ok(())
+    }
+}
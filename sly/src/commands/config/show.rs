@@ -0,0 +1,16 @@
+use anyhow::Result;
+use clap::Parser as Clap;
+
+use crate::lib::command::Command;
+use crate::lib::env::Env;
+
+#[derive(Clap)]
+pub struct ConfigShowOpts {}
+
+impl Command for ConfigShowOpts {
+    fn exec(self, env: &mut Env) -> Result<()> {
+        let workspace = env.workspace()?;
+        println!("{}", serde_json::to_string_pretty(&workspace.manifest)?);
+        Ok(())
+    }
+}
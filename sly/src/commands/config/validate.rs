@@ -0,0 +1,43 @@
+use anyhow::{bail, Result};
+use clap::Parser as Clap;
+use codespan_reporting::term::termcolor::{ColorChoice, StandardStream};
+
+use crate::lib::command::Command;
+use crate::lib::env::Env;
+use crate::lib::manifest_error::ManifestError;
+
+#[derive(Clap)]
+pub struct ConfigValidateOpts {}
+
+impl Command for ConfigValidateOpts {
+    fn exec(self, env: &mut Env) -> Result<()> {
+        let workspace = match env.workspace() {
+            Ok(workspace) => workspace,
+            Err(e) => match e.downcast_ref::<ManifestError>() {
+                Some(error) => {
+                    let writer = StandardStream::stderr(ColorChoice::Always);
+                    error.emit(&mut writer.lock())?;
+                    bail!("sly.json is not valid.");
+                }
+                None => return Err(e),
+            },
+        };
+
+        // Catch the dependency cycles as well, they are only detected on use otherwise.
+        let canisters = workspace.canisters.keys().cloned().collect::<Vec<_>>();
+        workspace.topological_order(&canisters)?;
+
+        for name in workspace.scripts.keys() {
+            workspace.script_order(name)?;
+        }
+
+        println!(
+            "sly.json is valid: {} canister(s), {} script(s), {} network(s).",
+            workspace.canisters.len(),
+            workspace.scripts.len(),
+            workspace.networks.len()
+        );
+
+        Ok(())
+    }
+}
@@ -6,14 +6,17 @@ use ic_agent::ic_types::Principal;
 use ic_agent::Agent;
 use ic_utils::call::AsyncCall;
 use ic_utils::interfaces::{ManagementCanister, Wallet};
-use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
-use std::io::{Read, Write};
 
 use crate::commands::call::waiter;
 use crate::lib::command::AsyncCommand;
 use crate::lib::env::Env;
 
+/// Amount of cycles a canister is created with on ephemeral networks.
+pub const PROVISIONAL_CYCLES: u64 = 100_000_000_000_000;
+
+/// Amount of cycles sent from the wallet to new canisters.
+pub const WALLET_CYCLES: u64 = 4_000_000_000_000;
+
 #[derive(Clap)]
 pub struct CreateCanisterOpts {
     /// Create a canister for all the canisters in sly.json.
@@ -23,9 +26,6 @@ pub struct CreateCanisterOpts {
     pub canisters: Vec<String>,
 }
 
-#[derive(Serialize, Deserialize)]
-struct CanisterIdJson(BTreeMap<String, BTreeMap<String, Principal>>);
-
 #[async_trait]
 impl AsyncCommand for CreateCanisterOpts {
     async fn async_exec(self, env: &mut Env) -> anyhow::Result<()> {
@@ -34,15 +34,13 @@ impl AsyncCommand for CreateCanisterOpts {
         }
 
         let workspace = env.workspace()?;
-        let host = env.network();
-        let use_provisional = host == "local";
-
-        // Use a different file for local env so people can gitignore it.
-        let filename = if host == "local" {
-            "canister_ids-local.json"
+        // Canisters on ephemeral networks are created with provisional cycles.
+        let wallet = if env.network_config()?.is_ephemeral() {
+            None
         } else {
-            "canister_ids.json"
+            Some(env.wallet()?)
         };
+        let store = env.canister_id_store()?;
 
         for name in &self.canisters {
             workspace
@@ -50,50 +48,52 @@ impl AsyncCommand for CreateCanisterOpts {
                 .ok_or_else(|| anyhow!("Canister '{}' not found.", name))?;
         }
 
-        let json_path = workspace.root.join(filename);
-        let mut file = std::fs::OpenOptions::new()
-            .write(true)
-            .create(true)
-            .read(true)
-            .open(json_path)
-            .with_context(|| format!("Failed to open {}", filename))?;
-
-        let mut json = String::new();
-        file.read_to_string(&mut json)
-            .with_context(|| format!("Failed to read {}", filename))?;
-
-        let mut canister_ids = if json.is_empty() {
-            CanisterIdJson(BTreeMap::new())
-        } else {
-            serde_json::from_str::<CanisterIdJson>(&json)
-                .with_context(|| format!("Failed to parse {}", filename))?
-        };
-
         // Name of the canisters we should create a canister for.
-        let canisters = if self.all {
+        let canisters: Vec<String> = if self.all {
             workspace.canisters.keys().cloned().collect()
         } else {
             self.canisters.clone()
         };
 
-        let agent = env.create_agent().await?;
+        let canisters =
+            workspace.filter_deployable(canisters, &env.network()?, &env.network_config()?);
 
+        let canister_ids = store.get_all()?;
         let to_create = canisters
             .into_iter()
-            .filter_map(|name| canister_ids.0.get(&name)?.get(&host).and(Some(name)))
+            .filter(|name| !canister_ids.contains_key(name))
             .collect::<Vec<_>>();
 
-        let futures = to_create
-            .iter()
-            .map(|_| create_canister(use_provisional, &agent));
+        if to_create.is_empty() {
+            return Ok(());
+        }
+
+        let mut controllers = Vec::with_capacity(to_create.len());
+        for name in &to_create {
+            let canister_controllers = env.canister_controllers(name)?;
+
+            // Nothing is created if any of the canisters would be out of our control.
+            if let Some(list) = &canister_controllers {
+                env.ensure_caller_is_controller(name, list)?;
+            }
+
+            controllers.push(canister_controllers);
+        }
+
+        let agent = env.create_agent().await?;
+
+        let futures = controllers
+            .into_iter()
+            .map(|controllers| create_canister(&agent, wallet, controllers));
         let new_canister_ids = join_all(futures).await;
         let mut had_error = false;
+        let mut created = Vec::new();
 
         for (name, maybe_principal) in to_create.into_iter().zip(new_canister_ids) {
-            let canister_ids = canister_ids.0.entry(name).or_default();
             match maybe_principal {
                 Ok(principal) => {
-                    canister_ids.insert(host.clone(), principal);
+                    log::info!("Created canister '{}' with id {}", name, principal);
+                    created.push((name, principal));
                 }
                 Err(e) => {
                     had_error = true;
@@ -102,12 +102,9 @@ impl AsyncCommand for CreateCanisterOpts {
             }
         }
 
-        let json = serde_json::to_string_pretty(&canister_ids)
-            .context("Failed to serialize canister ids.")?;
-        file.write(json.as_bytes())
-            .with_context(|| format!("Failed to write {}'s content.", filename))?;
-        file.set_len(json.len() as u64)
-            .with_context(|| format!("Failed to truncate {}", filename))?;
+        store
+            .update(|ids| ids.extend(created))
+            .with_context(|| format!("Failed to store the canister ids in {:?}", store.path()))?;
 
         if had_error {
             bail!("Some of the canisters were not created.")
@@ -117,38 +114,48 @@ impl AsyncCommand for CreateCanisterOpts {
     }
 }
 
-async fn create_canister(use_provisional: bool, agent: &Agent) -> anyhow::Result<Principal> {
-    if use_provisional {
-        log::trace!("Creating a canister using provisional_create_canister_with_cycles");
-
-        let management = ManagementCanister::create(agent);
-        let (canister_id,) = management
-            .create_canister()
-            .as_provisional_create_with_amount(Some(100_000_000_000_000))
-            .build()
-            .unwrap()
-            .call_and_wait(waiter::waiter_with_exponential_backoff())
-            .await
-            .context("provisional_create_canister_with_cycles call failed.")?;
-
-        Ok(canister_id)
-    } else {
-        log::trace!("Creating a canister using XTC wallet");
+/// Create a canister using the given wallet, or with provisional cycles if there is no wallet.
+async fn create_canister(
+    agent: &Agent,
+    wallet: Option<Principal>,
+    controllers: Option<Vec<Principal>>,
+) -> anyhow::Result<Principal> {
+    if let Some(wallet) = wallet {
+        log::trace!("Creating a canister using wallet {}", wallet);
 
-        let xtc = Principal::from_text("aanaa-xaaaa-aaaah-aaeiq-cai").unwrap();
-        let canister_id = Wallet::create(agent, xtc)
+        let canister_id = Wallet::create(agent, wallet)
             .wallet_create_canister(
-                4_000_000_000_000,
-                None,
+                WALLET_CYCLES,
+                controllers,
                 None,
                 None,
                 None,
                 waiter::waiter_with_exponential_backoff(),
             )
             .await
-            .context("XTC create canister call failed.")?
+            .context("Wallet create canister call failed.")?
             .canister_id;
 
+        Ok(canister_id)
+    } else {
+        log::trace!("Creating a canister using provisional_create_canister_with_cycles");
+
+        let management = ManagementCanister::create(agent);
+        let mut builder = management
+            .create_canister()
+            .as_provisional_create_with_amount(Some(PROVISIONAL_CYCLES));
+
+        for controller in controllers.unwrap_or_default() {
+            builder = builder.with_controller(controller);
+        }
+
+        let (canister_id,) = builder
+            .build()
+            .unwrap()
+            .call_and_wait(waiter::waiter_with_exponential_backoff())
+            .await
+            .context("provisional_create_canister_with_cycles call failed.")?;
+
         Ok(canister_id)
     }
 }
@@ -0,0 +1,33 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use clap::Parser as Clap;
+use ic_utils::call::AsyncCall;
+use ic_utils::interfaces::ManagementCanister;
+
+use crate::commands::call::waiter;
+use crate::lib::command::AsyncCommand;
+use crate::lib::env::Env;
+
+#[derive(Clap)]
+pub struct CyclesBalanceOpts {
+    /// Name or principal id of the canister, the current identity must be a controller.
+    canister: String,
+}
+
+#[async_trait]
+impl AsyncCommand for CyclesBalanceOpts {
+    async fn async_exec(self, env: &mut Env) -> Result<()> {
+        let canister_id = env.resolve_canister_id(&self.canister)?;
+
+        let agent = env.create_agent().await?;
+        let (status,) = ManagementCanister::create(&agent)
+            .canister_status(&canister_id)
+            .call_and_wait(waiter::waiter_with_exponential_backoff())
+            .await
+            .context("canister_status call failed.")?;
+
+        println!("{} cycles", status.cycles);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,25 @@
+use anyhow::Result;
+use clap::Parser as Clap;
+
+use crate::lib::command::{AsyncCommand, Command};
+use crate::lib::env::Env;
+
+pub mod balance;
+pub mod top_up;
+
+#[derive(Clap)]
+pub enum CyclesSubCommands {
+    /// Send cycles to a canister from the wallet.
+    TopUp(top_up::CyclesTopUpOpts),
+    /// Print the cycles balance of a canister.
+    Balance(balance::CyclesBalanceOpts),
+}
+
+impl Command for CyclesSubCommands {
+    fn exec(self, env: &mut Env) -> Result<()> {
+        match self {
+            CyclesSubCommands::TopUp(opts) => opts.exec(env),
+            CyclesSubCommands::Balance(opts) => opts.exec(env),
+        }
+    }
+}
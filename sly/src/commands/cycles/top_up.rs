@@ -0,0 +1,108 @@
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use candid::{CandidType, Decode, Deserialize, Encode, Nat, Principal};
+use clap::Parser as Clap;
+
+use crate::commands::call::waiter;
+use crate::lib::command::AsyncCommand;
+use crate::lib::env::Env;
+use crate::lib::utils;
+
+#[derive(Clap)]
+pub struct CyclesTopUpOpts {
+    /// Create the cycles out of thin air using `provisional_top_up_canister`, only
+    /// available on ephemeral networks.
+    #[clap(long)]
+    fabricate: bool,
+    /// Name or principal id of the canister to top up.
+    canister: String,
+    /// Amount of cycles to send, supports the K/M/B/T suffixes (e.g 1.5T).
+    #[clap(parse(try_from_str = utils::parse_cycles))]
+    amount: u64,
+}
+
+#[derive(CandidType, Deserialize)]
+struct WalletSendArgs {
+    canister: Principal,
+    amount: u64,
+}
+
+#[derive(CandidType, Deserialize)]
+struct ProvisionalTopUpArgs {
+    canister_id: Principal,
+    amount: Nat,
+}
+
+#[async_trait]
+impl AsyncCommand for CyclesTopUpOpts {
+    async fn async_exec(self, env: &mut Env) -> Result<()> {
+        let canister_id = env.resolve_canister_id(&self.canister)?;
+
+        if self.fabricate {
+            if !env.network_config()?.is_ephemeral() {
+                bail!("Cycles can only be fabricated on an ephemeral network.");
+            }
+
+            fabricate(env, canister_id, self.amount).await?;
+        } else {
+            wallet_send(env, canister_id, self.amount).await?;
+        }
+
+        println!("Sent {} cycles to {}", self.amount, canister_id);
+
+        Ok(())
+    }
+}
+
+async fn fabricate(env: &Env, canister_id: Principal, amount: u64) -> Result<()> {
+    log::trace!(
+        "Topping up {} using provisional_top_up_canister",
+        canister_id
+    );
+
+    let method_name = "provisional_top_up_canister";
+    let management = Principal::management_canister();
+    let arg = Encode!(&ProvisionalTopUpArgs {
+        canister_id,
+        amount: Nat::from(amount),
+    })?;
+    let effective_canister_id = utils::get_effective_canister_id(method_name, &arg, &management)
+        .context("Failed to get effective_canister_id for this call")?;
+
+    let agent = env.create_agent().await?;
+    agent
+        .update(&management, method_name)
+        .with_effective_canister_id(effective_canister_id)
+        .with_arg(&arg)
+        .call_and_wait(waiter::waiter_with_exponential_backoff())
+        .await
+        .context("provisional_top_up_canister call failed.")?;
+
+    Ok(())
+}
+
+async fn wallet_send(env: &Env, canister_id: Principal, amount: u64) -> Result<()> {
+    let wallet = env.wallet()?;
+    log::trace!("Sending cycles to {} using wallet {}", canister_id, wallet);
+
+    let arg = Encode!(&WalletSendArgs {
+        canister: canister_id,
+        amount,
+    })?;
+
+    let agent = env.create_agent().await?;
+    let response = agent
+        .update(&wallet, "wallet_send")
+        .with_arg(&arg)
+        .call_and_wait(waiter::waiter_with_exponential_backoff())
+        .await
+        .context("wallet_send call failed.")?;
+
+    if let Err(e) = Decode!(&response, Result<(), String>)
+        .context("Failed to decode the wallet_send response.")?
+    {
+        bail!("Wallet rejected the transfer: {}", e);
+    }
+
+    Ok(())
+}
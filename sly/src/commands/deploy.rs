@@ -1,32 +1,57 @@
 use crate::Command;
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use async_trait::async_trait;
 use clap::Parser as Clap;
+use humansize::{file_size_opts, FileSize};
+use ic_agent::AgentError;
 
 use crate::commands::build::BuildOpts;
-use crate::commands::create_canister::CreateCanisterOpts;
+use crate::commands::create_canister::{CreateCanisterOpts, PROVISIONAL_CYCLES, WALLET_CYCLES};
 use crate::commands::install_code::InstallOpts;
 use crate::lib::command::AsyncCommand;
 use crate::lib::env::Env;
+use crate::lib::shell;
+use crate::lib::utils;
+use crate::lib::wasm::WasmModule;
+use crate::lib::workspace::{CanisterHooks, Workspace};
 
 #[derive(Clap)]
 pub struct DeployOpts {
     /// The installation mode.
     #[clap(short, long, possible_values = & (["install", "reinstall", "upgrade"]), default_value = "install")]
-    mode: String,
+    pub mode: String,
     /// For conditional sly.json evaluation.
     #[clap(long, default_value = "default")]
-    with_mode: String,
+    pub with_mode: String,
     /// Install the code for all of the canisters in sly.json.
     #[clap(long)]
-    all: bool,
+    pub all: bool,
+    /// Install the code even if the deployed module is the same as the local wasm.
+    #[clap(long)]
+    pub force: bool,
+    /// Upgrade the canisters even if the new candid interface breaks the deployed one.
+    #[clap(long)]
+    pub allow_breaking: bool,
+    /// Only print what the deploy would do, without sending any update call.
+    #[clap(long)]
+    pub plan: bool,
+    /// The number of canisters to build in parallel, defaults to the number of CPUs.
+    #[clap(short, long)]
+    pub jobs: Option<usize>,
+    /// Run the build commands even if the inputs of a canister did not change.
+    #[clap(long)]
+    pub no_cache: bool,
     /// The canister to install.
-    canisters: Vec<String>,
+    pub canisters: Vec<String>,
 }
 
 #[async_trait]
 impl AsyncCommand for DeployOpts {
     async fn async_exec(self, env: &mut Env) -> Result<()> {
+        if self.plan {
+            return self.print_plan(env).await;
+        }
+
         let create_opts = CreateCanisterOpts {
             all: self.all,
             canisters: self.canisters.clone(),
@@ -35,6 +60,8 @@ impl AsyncCommand for DeployOpts {
         let build_opts = BuildOpts {
             with_mode: self.with_mode.clone(),
             all: self.all,
+            jobs: self.jobs,
+            no_cache: self.no_cache,
             canisters: self.canisters.clone(),
         };
 
@@ -42,6 +69,8 @@ impl AsyncCommand for DeployOpts {
             mode: self.mode.clone(),
             with_mode: self.with_mode.clone(),
             all: self.all,
+            force: self.force,
+            allow_breaking: self.allow_breaking,
             canisters: self.canisters.clone(),
         };
 
@@ -49,13 +78,172 @@ impl AsyncCommand for DeployOpts {
             .async_exec(env)
             .await
             .context("Encountered error in create canisters step")?;
+        self.run_hooks(env, "pre_build", |h| &h.pre_build)?;
         build_opts
             .exec(env)
             .context("Encountered error in build canisters step")?;
+        self.run_hooks(env, "post_build", |h| &h.post_build)?;
+        self.run_hooks(env, "pre_install", |h| &h.pre_install)?;
         install_opts
             .async_exec(env)
             .await
             .context("Encountered error in install canisters step")?;
+        self.run_hooks(env, "post_install", |h| &h.post_install)?;
+
+        Ok(())
+    }
+}
+
+impl DeployOpts {
+    /// Return the canisters that should be deployed, sorted by their dependencies.
+    fn get_canisters(&self, workspace: &Workspace) -> Result<Vec<String>> {
+        if !self.canisters.is_empty() && self.all {
+            bail!("Unexpect canisters list when --all is provided.");
+        }
+
+        let canisters: Vec<String> = if self.all {
+            workspace.canisters.keys().cloned().collect()
+        } else {
+            self.canisters.clone()
+        };
+
+        workspace.topological_order(&canisters)
+    }
+
+    /// Run the given hook for every canister that is being deployed, a failing hook aborts
+    /// the deploy.
+    fn run_hooks<F>(&self, env: &Env, hook: &str, get_commands: F) -> Result<()>
+    where
+        F: Fn(&CanisterHooks) -> &Vec<String>,
+    {
+        let workspace = env.workspace()?;
+        let canister_ids = env.canister_ids()?;
+        let network = env.network()?;
+        let network_config = env.network_config()?;
+
+        for name in self.get_canisters(&workspace)? {
+            let canister = workspace.get_canister(&name).unwrap();
+            let commands = get_commands(&canister.hooks);
+
+            if commands.is_empty() || canister.is_remote_on(&network, &network_config) {
+                continue;
+            }
+
+            let mut envs = env.command_envs(&name, &self.with_mode)?;
+            envs.push(("SLY_INSTALL_MODE".to_string(), self.mode.clone()));
+
+            if let Some(canister_id) = canister_ids.get(&name) {
+                envs.push(("SLY_CANISTER_ID".to_string(), canister_id.to_text()));
+            }
+
+            if let Some(wasm_path) = workspace.get_wasm_path(&name, &self.with_mode) {
+                envs.push((
+                    "SLY_WASM_PATH".to_string(),
+                    wasm_path.to_string_lossy().to_string(),
+                ));
+            }
+
+            for command in commands {
+                log::info!("Running {} hook for canister '{}': {}", hook, name, command);
+                shell::run_captured(command, &workspace.root, envs.clone())
+                    .with_context(|| format!("The {} hook of canister '{}' failed.", hook, name))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Print the steps that the deploy is going to take for each canister.
+    async fn print_plan(&self, env: &Env) -> Result<()> {
+        let workspace = env.workspace()?;
+        let canister_ids = env.canister_ids()?;
+        let network = env.network()?;
+        let network_config = env.network_config()?;
+        let is_ephemeral = network_config.is_ephemeral();
+        let canisters = self.get_canisters(&workspace)?;
+
+        // Only connect to the network if there is a deployed canister to inspect.
+        let agent = if canisters.iter().any(|name| canister_ids.contains_key(name)) {
+            Some(env.create_agent().await?)
+        } else {
+            None
+        };
+
+        println!("Deploy plan for network '{}':", network);
+
+        for name in canisters {
+            let canister = workspace.get_canister(&name).unwrap();
+            let canister_id = canister_ids.get(&name);
+
+            println!();
+            println!("{}", name);
+
+            if canister.is_remote_on(&network, &network_config) {
+                match canister_id {
+                    Some(canister_id) => println!("  Remote:       {}", canister_id),
+                    None => println!("  Remote:       (no id for network '{}')", network),
+                }
+                continue;
+            }
+
+            match canister_id {
+                Some(canister_id) => println!("  Canister id:  {}", canister_id),
+                None if is_ephemeral => println!(
+                    "  Create:       yes, with {} provisional cycles",
+                    PROVISIONAL_CYCLES
+                ),
+                None => println!(
+                    "  Create:       yes, with {} cycles from wallet {}",
+                    WALLET_CYCLES,
+                    env.wallet()?
+                ),
+            }
+
+            match canister.build.get(&self.with_mode) {
+                Some(commands) => {
+                    for (i, command) in commands.iter().enumerate() {
+                        let label = if i == 0 { "Build:" } else { "" };
+                        println!("  {:<13} {}", label, command);
+                    }
+                }
+                None => println!(
+                    "  Build:        (no build command for mode '{}')",
+                    self.with_mode
+                ),
+            }
+
+            match workspace.get_wasm_path(&name, &self.with_mode) {
+                Some(wasm_path) => {
+                    let path = pathdiff::diff_paths(&wasm_path, &workspace.root)
+                        .unwrap_or_else(|| wasm_path.clone());
+                    let path = path.to_string_lossy();
+                    match WasmModule::load(&wasm_path, canister.compress) {
+                        Ok(wasm) => println!(
+                            "  Wasm:         {} ({}{}, sha256 {})",
+                            path,
+                            wasm.bytes.len().file_size(file_size_opts::BINARY).unwrap(),
+                            if wasm.gzipped { " gzip" } else { "" },
+                            hex::encode(utils::sha256(&wasm.bytes))
+                        ),
+                        Err(_) => println!("  Wasm:         {} (not built yet)", path),
+                    }
+                }
+                None => println!("  Wasm:         (no wasm for mode '{}')", self.with_mode),
+            }
+
+            println!("  Install mode: {}", self.mode);
+
+            if let (Some(agent), Some(canister_id)) = (&agent, canister_id) {
+                match agent
+                    .read_state_canister_info(*canister_id, "module_hash")
+                    .await
+                {
+                    Ok(hash) => println!("  Deployed:     sha256 {}", hex::encode(hash)),
+                    Err(AgentError::LookupPathAbsent(_)) => println!("  Deployed:     (empty)"),
+                    Err(e) => println!("  Deployed:     (unknown: {})", e),
+                }
+            }
+        }
 
         Ok(())
     }
@@ -0,0 +1,42 @@
+use anyhow::Result;
+use clap::Parser as Clap;
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::lib::command::Command;
+use crate::lib::env::Env;
+
+#[derive(Clap)]
+pub struct HistoryOpts {
+    /// Name of the canister.
+    canister: String,
+}
+
+impl Command for HistoryOpts {
+    fn exec(self, env: &mut Env) -> Result<()> {
+        let deployments = env.deployment_history()?.list_canister(&self.canister)?;
+
+        if deployments.is_empty() {
+            println!(
+                "No deployments of '{}' on network '{}'.",
+                self.canister,
+                env.network()?
+            );
+            return Ok(());
+        }
+
+        for (index, deployment) in deployments.iter().enumerate() {
+            let time = UNIX_EPOCH + Duration::from_secs(deployment.timestamp);
+            println!(
+                "#{} {} {} wasm={} commit={} identity={}",
+                index,
+                humantime::format_rfc3339_seconds(time),
+                deployment.mode,
+                deployment.wasm_sha256,
+                deployment.git_commit.as_deref().unwrap_or("-"),
+                deployment.identity
+            );
+        }
+
+        Ok(())
+    }
+}
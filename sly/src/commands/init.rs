@@ -0,0 +1,134 @@
+use anyhow::{bail, Context, Result};
+use candid::Principal;
+use clap::Parser as Clap;
+use std::path::Path;
+
+use crate::lib::canister_ids::CanisterIdStore;
+use crate::lib::command::Command;
+use crate::lib::dfx_import;
+use crate::lib::env::Env;
+use crate::lib::network::Network;
+use crate::lib::utils;
+use crate::lib::workspace::Workspace;
+
+#[derive(Clap)]
+pub struct InitOpts {
+    /// Translate the dfx.json in the current directory and migrate the canister ids of the
+    /// dfx project.
+    #[clap(long)]
+    from_dfx: bool,
+    /// Overwrite the existing sly.json.
+    #[clap(long)]
+    force: bool,
+}
+
+impl Command for InitOpts {
+    fn exec(self, _: &mut Env) -> Result<()> {
+        let root =
+            std::env::current_dir().context("Failed to retrieve current working directory.")?;
+        let path = root.join("sly.json");
+
+        if path.exists() && !self.force {
+            bail!("sly.json already exists, use --force to overwrite it.");
+        }
+
+        if !self.from_dfx {
+            let manifest = serde_json::json!({ "canisters": {} });
+            write_manifest(&path, &manifest)?;
+            println!("Created sly.json.");
+            return Ok(());
+        }
+
+        let dfx_path = root.join("dfx.json");
+        let source = std::fs::read_to_string(&dfx_path)
+            .context("Failed to read dfx.json in the current directory.")?;
+        let mut import = dfx_import::translate(&source)?;
+
+        // Check the translation and every id file before anything is written, so a failure
+        // does not leave a half-migrated project behind.
+        let content = manifest_content(&import.manifest)?;
+        Workspace::from_reader(root.clone(), content.as_bytes())
+            .context("The sly.json translated from dfx.json is not valid.")?;
+        let canister_ids = read_canister_ids(&root, &import.manifest, &mut import.warnings)?;
+
+        utils::write_atomic(&path, content.as_bytes())?;
+        println!(
+            "Created sly.json with {} canister(s).",
+            import.manifest["canisters"].as_object().unwrap().len()
+        );
+
+        let migrated = canister_ids.len();
+        for (name, network, is_ephemeral, canister_id) in canister_ids {
+            CanisterIdStore::new(&root, &network, is_ephemeral).set(&name, canister_id)?;
+        }
+        println!("Migrated {} canister id(s).", migrated);
+
+        if !import.warnings.is_empty() {
+            println!();
+            println!("Could not translate:");
+            for warning in &import.warnings {
+                println!("  - {}", warning);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn manifest_content(manifest: &serde_json::Value) -> Result<String> {
+    let mut content = serde_json::to_string_pretty(manifest)?;
+    content.push('\n');
+    Ok(content)
+}
+
+fn write_manifest(path: &Path, manifest: &serde_json::Value) -> Result<()> {
+    utils::write_atomic(path, manifest_content(manifest)?.as_bytes())
+}
+
+/// Read the canister ids of the dfx project that can be migrated, as tuples of the canister
+/// name, the network, whether the network is ephemeral and the canister id.
+fn read_canister_ids(
+    root: &Path,
+    manifest: &serde_json::Value,
+    warnings: &mut Vec<String>,
+) -> Result<Vec<(String, String, bool, Principal)>> {
+    let mut canister_ids = Vec::new();
+
+    for file in dfx_import::canister_id_files(root)? {
+        let display = pathdiff::diff_paths(&file, root).unwrap_or_else(|| file.clone());
+
+        for (name, ids) in dfx_import::read_canister_ids(&file)? {
+            if manifest["canisters"].get(&name).is_none() {
+                warnings.push(format!(
+                    "{}: canister '{}' is not in dfx.json, its ids are dropped.",
+                    display.to_string_lossy(),
+                    name
+                ));
+                continue;
+            }
+
+            for (network, canister_id) in ids {
+                let is_ephemeral = match manifest["networks"].get(&network) {
+                    Some(info) => info["type"] == "ephemeral",
+                    None => match Network::predefined(&network) {
+                        Some(network) => network.is_ephemeral(),
+                        None => {
+                            warnings.push(format!(
+                                "{}: the network '{}' of canister '{}' is not translated, its \
+                                 id is dropped.",
+                                display.to_string_lossy(),
+                                network,
+                                name
+                            ));
+                            continue;
+                        }
+                    },
+                };
+
+                canister_ids.push((name.clone(), network, is_ephemeral, canister_id));
+            }
+        }
+    }
+
+    Ok(canister_ids)
+}
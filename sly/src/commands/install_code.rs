@@ -3,16 +3,23 @@ use async_trait::async_trait;
 use candid::Principal;
 use clap::Parser as Clap;
 use futures::future::join_all;
-use ic_agent::Agent;
+use ic_agent::ic_types::hash_tree::Label;
+use ic_agent::{lookup_value, Agent};
 use ic_utils::call::AsyncCall;
+use ic_utils::interfaces::management_canister::builders::InstallMode;
 use ic_utils::interfaces::ManagementCanister;
-use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-use crate::commands::call::waiter;
+use crate::commands::call::{helper, waiter};
+use crate::lib::assets;
+use crate::lib::candid_compat::{find_breaking_changes, Service};
 use crate::lib::command::AsyncCommand;
 use crate::lib::env::Env;
+use crate::lib::utils;
+use crate::lib::wasm::WasmModule;
+use crate::lib::workspace::CanisterKind;
 
 #[derive(Clap)]
 pub struct InstallOpts {
@@ -25,13 +32,16 @@ pub struct InstallOpts {
     /// Install the code for all of the canisters in sly.json.
     #[clap(long)]
     pub all: bool,
+    /// Install the code even if the deployed module is the same as the local wasm.
+    #[clap(long)]
+    pub force: bool,
+    /// Upgrade the canisters even if the new candid interface breaks the deployed one.
+    #[clap(long)]
+    pub allow_breaking: bool,
     /// The canister to install.
     pub canisters: Vec<String>,
 }
 
-#[derive(Serialize, Deserialize)]
-struct CanisterIdJson(BTreeMap<String, BTreeMap<String, Principal>>);
-
 #[async_trait]
 impl AsyncCommand for InstallOpts {
     async fn async_exec(self, env: &mut Env) -> anyhow::Result<()> {
@@ -40,57 +50,50 @@ impl AsyncCommand for InstallOpts {
         }
 
         let workspace = env.workspace()?;
-        let host = env.network();
-        let filename = if host == "local" {
-            "canister_ids-local.json"
-        } else {
-            "canister_ids.json"
-        };
+        let canister_ids = env.canister_ids()?;
 
-        let json_path = workspace.root.join(filename);
-        let json = std::fs::read_to_string(json_path).with_context(|| {
-            format!(
-                "Could not read {}. Run 'sly create_canister' first.",
-                filename
-            )
-        })?;
-        let canister_ids = serde_json::from_str::<CanisterIdJson>(&json)
-            .with_context(|| format!("Could not parse {}", filename))?;
-
-        let canisters = if self.all {
+        let canisters: Vec<String> = if self.all {
             workspace.canisters.keys().cloned().collect()
         } else {
             self.canisters.clone()
         };
 
-        let mut to_install = vec![];
+        let canisters =
+            workspace.filter_deployable(canisters, &env.network()?, &env.network_config()?);
+
+        let mode = InstallMode::from_str(&self.mode).map_err(|e| anyhow!(e))?;
+        // Reinstalling is used to reset the state of a canister, so we never skip it.
+        let skip_unchanged = !self.force && self.mode != "reinstall";
 
-        for name in canisters.clone() {
+        let levels = workspace.topological_levels(&canisters)?;
+        let mut to_install = BTreeMap::new();
+        let mut services = BTreeMap::new();
+
+        for name in canisters {
             let canister = workspace
                 .get_canister(&name)
                 .ok_or_else(|| anyhow!("Canister '{}' not found.", name))?;
 
-            let canister_id = canister_ids
-                .0
-                .get(&name)
-                .map(|m| m.get(&host))
-                .flatten()
-                .ok_or_else(|| {
-                    anyhow!(
-                        "Canister '{}' is not created. Please use sly create first.",
-                        name
-                    )
-                })?;
-
-            let wasm_path = canister.wasm.get(&self.with_mode).ok_or_else(|| {
+            let canister_id = canister_ids.get(&name).ok_or_else(|| {
                 anyhow!(
-                    "Canister '{}' does not have a wasm field for mode '{}'.",
-                    name,
-                    self.with_mode
+                    "Canister '{}' is not created. Please use sly create first.",
+                    name
                 )
             })?;
 
-            let wasm_path = workspace.root.join(PathBuf::from(wasm_path));
+            let wasm_path = workspace
+                .get_wasm_path(&name, &self.with_mode)
+                .ok_or_else(|| match canister.kind {
+                    CanisterKind::Assets { .. } => anyhow!(
+                        "Could not find the assets canister wasm in the toolchain for '{}', set the wasm field in sly.json.",
+                        name
+                    ),
+                    _ => anyhow!(
+                        "Canister '{}' does not have a wasm field for mode '{}'.",
+                        name,
+                        self.with_mode
+                    )
+                })?;
 
             if !wasm_path.is_file() {
                 bail!(
@@ -100,38 +103,252 @@ impl AsyncCommand for InstallOpts {
                 )
             }
 
-            let wasm = std::fs::read(&wasm_path)
-                .with_context(|| format!("Could not read '{}'", wasm_path.to_string_lossy()))?;
+            let wasm = WasmModule::load(&wasm_path, canister.compress)?;
+            wasm.check_ingress_size(&name, 0)?;
 
-            to_install.push((*canister_id, wasm));
+            if let Some(candid_path) = workspace.get_candid_path(&name, &self.with_mode) {
+                match load_service(&candid_path) {
+                    Ok(service) => {
+                        services.insert(name.clone(), service);
+                    }
+                    Err(e) if self.mode == "upgrade" => return Err(e),
+                    Err(e) => log::warn!("Could not load the candid for '{}': {}", name, e),
+                }
+            }
+
+            to_install.insert(name, (*canister_id, wasm.bytes));
         }
 
         let agent = env.create_agent().await?;
 
-        let futures = to_install
-            .into_iter()
-            .map(|(canister_id, wasm)| install_code(&agent, canister_id, wasm))
-            .collect::<Vec<_>>();
+        if self.mode == "upgrade" {
+            let mut is_breaking = false;
+
+            for (name, service) in &services {
+                let canister_id = to_install[name].0;
+                let old = match get_deployed_service(env, &agent, name, canister_id).await? {
+                    Some(old) => old,
+                    None => {
+                        log::warn!(
+                            "Could not find the deployed candid interface of '{}', skipping the compatibility check.",
+                            name
+                        );
+                        continue;
+                    }
+                };
+
+                let changes = find_breaking_changes(service, &old);
+                if changes.is_empty() {
+                    continue;
+                }
+
+                is_breaking = true;
+                eprintln!("Canister '{}' has breaking interface changes:", name);
+                for change in changes {
+                    eprintln!("  {}: {}", change.method, change.reason);
+                }
+            }
+
+            if is_breaking && !self.allow_breaking {
+                bail!("Refusing to upgrade with breaking candid changes. Use --allow-breaking to upgrade anyway.");
+            }
+        }
+        let history = env.deployment_history()?;
+        let identity = env.current_identity().sender().map_err(|e| anyhow!(e))?;
+
+        let mut installed = Vec::new();
+        let mut skipped = Vec::new();
+
+        // Install the canisters level by level, so dependencies are installed before the
+        // canisters that depend on them.
+        for level in levels {
+            let futures = level
+                .iter()
+                .map(|name| {
+                    let (canister_id, wasm) = &to_install[name];
+                    install_if_changed(&agent, *canister_id, wasm, mode, skip_unchanged)
+                })
+                .collect::<Vec<_>>();
+
+            let mut had_error = false;
+            for (name, result) in level.iter().cloned().zip(join_all(futures).await) {
+                if result.is_ok() {
+                    if let Some(service) = services.get(&name) {
+                        save_deployed_service(env, &name, service)?;
+                    }
+                }
+
+                match result {
+                    Ok(true) => {
+                        let wasm = &to_install[&name].1;
+                        // The code is installed, so the other canisters are still processed.
+                        if let Err(e) =
+                            history.record(&workspace.root, &name, &self.mode, identity, wasm)
+                        {
+                            log::warn!(
+                                "Could not record the deployment of canister '{}': {:#}",
+                                name,
+                                e
+                            );
+                        }
+                        installed.push(name);
+                    }
+                    Ok(false) => {
+                        log::info!("Skipping canister '{}', the wasm is unchanged.", name);
+                        skipped.push(name);
+                    }
+                    Err(e) => {
+                        had_error = true;
+                        log::error!("Failed to install code for canister '{}': {}", name, e);
+                    }
+                }
+            }
+
+            if had_error {
+                bail!("Failed to install code for some of the canisters.");
+            }
+
+            // Upload the files of the assets canisters, the files can change even if the wasm
+            // does not.
+            for name in &level {
+                if let CanisterKind::Assets { source } = &workspace.get_canister(name).unwrap().kind
+                {
+                    let canister_id = to_install[name].0;
+                    let summary = assets::sync(&agent, canister_id, &workspace.root, source)
+                        .await
+                        .with_context(|| format!("Failed to upload the assets of '{}'.", name))?;
 
-        let mut had_error = false;
-        for (name, result) in canisters.into_iter().zip(join_all(futures).await) {
-            if let Err(e) = result {
-                had_error = true;
-                log::error!("Failed to install code for canister '{}': {}", name, e);
+                    println!(
+                        "Synced the assets of '{}': {} uploaded, {} unchanged, {} deleted.",
+                        name, summary.uploaded, summary.unchanged, summary.deleted
+                    );
+                }
             }
         }
 
-        if had_error {
-            bail!("Failed to install code for some of the canisters.");
+        if !installed.is_empty() {
+            println!("Installed: {}", installed.join(", "));
+        }
+
+        if !skipped.is_empty() {
+            println!("Skipped (unchanged): {}", skipped.join(", "));
         }
 
         Ok(())
     }
 }
 
-async fn install_code(agent: &Agent, canister_id: Principal, wasm: Vec<u8>) -> anyhow::Result<()> {
+/// Install the wasm on the canister, unless `skip_unchanged` is set and the canister already
+/// runs the same module. Returns whether the code was installed.
+async fn install_if_changed(
+    agent: &Agent,
+    canister_id: Principal,
+    wasm: &[u8],
+    mode: InstallMode,
+    skip_unchanged: bool,
+) -> anyhow::Result<bool> {
+    if skip_unchanged {
+        let hash = utils::sha256(wasm);
+        if get_module_hash(agent, canister_id).await? == Some(hash) {
+            return Ok(false);
+        }
+    }
+
+    install_code(agent, canister_id, wasm, mode).await?;
+
+    Ok(true)
+}
+
+/// Load the candid service from the given file.
+fn load_service(path: &Path) -> anyhow::Result<Service> {
+    let (env, actor) = helper::check_candid_file(&path.to_string_lossy())?;
+    Service::new(env, actor).with_context(|| format!("Invalid candid file {:?}", path))
+}
+
+/// Return the candid interface that is currently deployed on the canister, either from the
+/// `candid:service` metadata of the canister or from the copy stored after the last install.
+async fn get_deployed_service(
+    env: &Env,
+    agent: &Agent,
+    name: &str,
+    canister_id: Principal,
+) -> anyhow::Result<Option<Service>> {
+    if let Some(source) = get_candid_metadata(agent, canister_id).await {
+        log::trace!("Using the candid:service metadata of '{}'", name);
+        return Service::from_source(&source).map(Some);
+    }
+
+    let path = deployed_candid_path(env, name)?;
+    if !path.is_file() {
+        return Ok(None);
+    }
+
+    log::trace!("Using the stored candid interface {:?}", path);
+    let source =
+        std::fs::read_to_string(&path).with_context(|| format!("Could not read {:?}", path))?;
+    Service::from_source(&source).map(Some)
+}
+
+/// Read the `candid:service` metadata section of the canister.
+async fn get_candid_metadata(agent: &Agent, canister_id: Principal) -> Option<String> {
+    let path: Vec<Label> = vec![
+        "canister".into(),
+        canister_id.as_slice().into(),
+        "metadata".into(),
+        "candid:service".into(),
+    ];
+
+    let certificate = agent
+        .read_state_raw(vec![path.clone()], canister_id)
+        .await
+        .ok()?;
+    let bytes = lookup_value(&certificate, path).ok()?;
+
+    String::from_utf8(bytes.to_vec()).ok()
+}
+
+/// Store the candid interface of an installed canister, used for the compatibility checks
+/// of the next upgrades.
+fn save_deployed_service(env: &Env, name: &str, service: &Service) -> anyhow::Result<()> {
+    let path = deployed_candid_path(env, name)?;
+    std::fs::create_dir_all(path.parent().unwrap())
+        .with_context(|| format!("Could not create the directory for {:?}", path))?;
+    std::fs::write(&path, service.to_source())
+        .with_context(|| format!("Could not write {:?}", path))
+}
+
+fn deployed_candid_path(env: &Env, name: &str) -> anyhow::Result<PathBuf> {
+    Ok(env
+        .state_dir()?
+        .join("candid")
+        .join(utils::network_file_name(&env.network()?))
+        .join(format!("{}.did", name)))
+}
+
+/// Return the sha256 of the module currently installed on the canister.
+pub async fn get_module_hash(
+    agent: &Agent,
+    canister_id: Principal,
+) -> anyhow::Result<Option<Vec<u8>>> {
+    let (status,) = ManagementCanister::create(agent)
+        .canister_status(&canister_id)
+        .call_and_wait(waiter::waiter_with_exponential_backoff())
+        .await
+        .context("canister_status call failed.")?;
+
+    Ok(status.module_hash)
+}
+
+/// Install the given module on the canister.
+pub async fn install_code(
+    agent: &Agent,
+    canister_id: Principal,
+    wasm: &[u8],
+    mode: InstallMode,
+) -> anyhow::Result<()> {
     ManagementCanister::create(agent)
-        .install_code(&canister_id, &wasm)
+        .install_code(&canister_id, wasm)
+        .with_mode(mode)
         .build()
         .unwrap()
         .call_and_wait(waiter::waiter_with_exponential_backoff())
@@ -8,22 +8,31 @@ use crate::lib::env::Env;
 mod build;
 mod call;
 mod candid;
+mod canister;
+mod clean;
+mod config;
 mod create_canister;
+mod cycles;
 mod deploy;
+mod history;
 mod identity;
+mod init;
 mod install_code;
 mod new;
 mod principal;
 mod replica;
+mod rollback;
+mod run;
+mod test;
 mod wasm;
 
 /// Psychedelic's CLI for the Internet Computer.
 #[derive(Clap)]
 #[clap(version = "0.1", author = "Psychedelic Team")]
 pub struct App {
-    /// The network to use when making calls to the I.C.
-    #[clap(short, long, default_value = "local")]
-    pub network: String,
+    /// The network to use when making calls to the I.C, defaults to local.
+    #[clap(short, long)]
+    pub network: Option<String>,
     /// The identity that should be used. This overwrites the
     /// default identity.
     #[clap(long)]
@@ -31,6 +40,9 @@ pub struct App {
     /// Optional path to the sly.json file.
     #[clap(long)]
     pub config: Option<PathBuf>,
+    /// The profile of sly.json whose overrides should be applied.
+    #[clap(long, global = true)]
+    pub profile: Option<String>,
     /// A level of verbosity, can be used multiple times.
     #[clap(short, long, parse(from_occurrences))]
     pub verbose: i32,
@@ -43,25 +55,46 @@ pub enum AppSubCommands {
     /// The commands to deal with Candid IDL files and values.
     #[clap(subcommand)]
     Candid(candid::CandidSubCommands),
+    /// Set of commands to manage the canister ids of the workspace.
+    #[clap(subcommand)]
+    Canister(canister::CanisterSubCommands),
+    /// Set of commands to inspect the sly.json of the workspace.
+    #[clap(subcommand)]
+    Config(config::ConfigSubCommands),
     /// Set of commands to manage the identities used by this program.
     #[clap(subcommand)]
     Identity(identity::IdentitySubCommands),
     /// Set of commands to manage the local replica and run management methods.
     #[clap(subcommand)]
     Replica(replica::ReplicaSubCommands),
+    /// Commands to manage the cycles balance of canisters.
+    #[clap(subcommand)]
+    Cycles(cycles::CyclesSubCommands),
     /// Utilities to work with WASM files.
     #[clap(subcommand)]
     Wasm(wasm::WasmSubCommands),
     /// Create a new project.
     New(new::NewOpts),
+    /// Create a sly.json in the current directory, optionally from an existing dfx project.
+    Init(init::InitOpts),
     /// Install the code for the given canister(s).
     InstallCode(install_code::InstallOpts),
     /// Build the wasm for the given canister(s).
     Build(build::BuildOpts),
+    /// Remove the build cache and the built wasm files.
+    Clean(clean::CleanOpts),
     /// Build the wasm for the given canister(s).
     CreateCanister(create_canister::CreateCanisterOpts),
     /// Deploy the canisters of the current workspace.
     Deploy(deploy::DeployOpts),
+    /// List the deployments of a canister on the current network.
+    History(history::HistoryOpts),
+    /// Upgrade a canister back to a previously deployed module.
+    Rollback(rollback::RollbackOpts),
+    /// Run the test commands of the given canister(s).
+    Test(test::TestOpts),
+    /// Run a script of the current workspace and the scripts it depends on.
+    Run(run::RunOpts),
     /// Search for a given principal id.
     PrincipalGen(principal::PrincipalOpts),
     /// Canister call
@@ -72,14 +105,23 @@ impl Command for AppSubCommands {
     fn exec(self, env: &mut Env) -> Result<()> {
         match self {
             AppSubCommands::Candid(sub) => sub.exec(env),
+            AppSubCommands::Canister(sub) => sub.exec(env),
+            AppSubCommands::Config(sub) => sub.exec(env),
             AppSubCommands::Identity(sub) => sub.exec(env),
             AppSubCommands::Replica(sub) => sub.exec(env),
+            AppSubCommands::Cycles(sub) => sub.exec(env),
             AppSubCommands::Wasm(sub) => sub.exec(env),
             AppSubCommands::New(opts) => opts.exec(env),
+            AppSubCommands::Init(opts) => opts.exec(env),
             AppSubCommands::InstallCode(opts) => opts.exec(env),
             AppSubCommands::Build(opts) => opts.exec(env),
+            AppSubCommands::Clean(opts) => opts.exec(env),
             AppSubCommands::CreateCanister(opts) => opts.exec(env),
             AppSubCommands::Deploy(opts) => opts.exec(env),
+            AppSubCommands::History(opts) => opts.exec(env),
+            AppSubCommands::Rollback(opts) => opts.exec(env),
+            AppSubCommands::Test(opts) => opts.exec(env),
+            AppSubCommands::Run(opts) => opts.exec(env),
             AppSubCommands::PrincipalGen(opts) => opts.exec(env),
             AppSubCommands::Call(opts) => opts.exec(env),
         }
@@ -5,6 +5,7 @@ use clap::Parser as Clap;
 use crate::actors::{start_replica, start_shutdown_controller};
 use crate::lib::command::AsyncCommand;
 use crate::lib::env::Env;
+use crate::lib::toolchain;
 
 #[derive(Clap)]
 pub struct ReplicaStartOpts {
@@ -19,7 +20,8 @@ impl AsyncCommand for ReplicaStartOpts {
 
     async fn async_exec(self, _env: &mut Env) -> Result<()> {
         let shutdown_controller = start_shutdown_controller()?;
-        start_replica(Some(shutdown_controller), self.no_artificial_delay)?;
+        let root = toolchain::get_replica_state_root()?;
+        start_replica(Some(shutdown_controller), self.no_artificial_delay, &root)?;
         Ok(())
     }
 }
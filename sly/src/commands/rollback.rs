@@ -0,0 +1,76 @@
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use clap::Parser as Clap;
+use ic_utils::interfaces::management_canister::builders::InstallMode;
+
+use crate::commands::install_code::install_code;
+use crate::lib::command::AsyncCommand;
+use crate::lib::env::Env;
+
+#[derive(Clap)]
+pub struct RollbackOpts {
+    /// Name of the canister.
+    canister: String,
+    /// The index of the deployment to roll back to, as shown by `sly history`. Defaults
+    /// to the deployment before the latest one.
+    #[clap(long)]
+    to: Option<usize>,
+}
+
+#[async_trait]
+impl AsyncCommand for RollbackOpts {
+    async fn async_exec(self, env: &mut Env) -> Result<()> {
+        let history = env.deployment_history()?;
+        let deployments = history.list_canister(&self.canister)?;
+
+        let index = match self.to {
+            Some(index) => index,
+            None if deployments.len() >= 2 => deployments.len() - 2,
+            None => bail!(
+                "Canister '{}' does not have a previous deployment to roll back to.",
+                self.canister
+            ),
+        };
+
+        let deployment = deployments.get(index).ok_or_else(|| {
+            anyhow!(
+                "Deployment #{} of canister '{}' does not exist.",
+                index,
+                self.canister
+            )
+        })?;
+
+        let canister_id = env
+            .canister_ids()?
+            .get(&self.canister)
+            .cloned()
+            .ok_or_else(|| anyhow!("Canister '{}' is not created.", self.canister))?;
+
+        let wasm = history.read_wasm(deployment)?;
+        let identity = env.current_identity().sender().map_err(|e| anyhow!(e))?;
+
+        log::info!(
+            "Rolling back '{}' to deployment #{} ({})",
+            self.canister,
+            index,
+            deployment.wasm_sha256
+        );
+
+        let agent = env.create_agent().await?;
+        install_code(&agent, canister_id, &wasm, InstallMode::Upgrade).await?;
+
+        let workspace = env.workspace()?;
+        if let Err(e) = history.record(&workspace.root, &self.canister, "rollback", identity, &wasm)
+        {
+            log::warn!(
+                "Could not record the rollback of '{}': {:#}",
+                self.canister,
+                e
+            );
+        }
+
+        println!("Rolled back '{}' to deployment #{}.", self.canister, index);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,60 @@
+use anyhow::{Context, Result};
+use clap::Parser as Clap;
+
+use crate::lib::command::Command;
+use crate::lib::env::Env;
+use crate::lib::shell;
+
+#[derive(Clap)]
+pub struct RunOpts {
+    /// The value of SLY_MODE in the environment of the scripts.
+    #[clap(long, default_value = "default")]
+    with_mode: String,
+    /// Name of the script.
+    script: String,
+    /// Extra arguments that are appended to the commands of the script.
+    #[clap(last = true)]
+    args: Vec<String>,
+}
+
+impl Command for RunOpts {
+    fn exec(self, env: &mut Env) -> Result<()> {
+        let workspace = env.workspace()?;
+        let order = workspace.script_order(&self.script)?;
+
+        let args = self
+            .args
+            .iter()
+            .map(|arg| shell::quote(arg))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        for name in &order {
+            let script = &workspace.scripts[name];
+            let script_env =
+                env.for_script(script.network.as_deref(), script.identity.as_deref())?;
+            let envs = script_env.workspace_envs(&self.with_mode)?;
+            let prefix = format!("[{}]", name);
+
+            log::info!(
+                "Running script '{}' on network '{}'.",
+                name,
+                script_env.network()?
+            );
+
+            for command in &script.commands {
+                // Only the requested script receives the extra arguments.
+                let command = if name == &self.script && !args.is_empty() {
+                    format!("{} {}", command, args)
+                } else {
+                    command.clone()
+                };
+
+                shell::run_prefixed(&command, &workspace.root, envs.iter().cloned(), &prefix)
+                    .with_context(|| format!("Script '{}' failed.", name))?;
+            }
+        }
+
+        Ok(())
+    }
+}
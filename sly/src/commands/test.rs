@@ -0,0 +1,204 @@
+use actix::Addr;
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use clap::Parser as Clap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::actors::shutdown_controller::signals::ShutdownTrigger;
+use crate::actors::shutdown_controller::ShutdownController;
+use crate::actors::{start_replica, start_shutdown_controller};
+use crate::commands::deploy::DeployOpts;
+use crate::lib::command::AsyncCommand;
+use crate::lib::env::Env;
+use crate::lib::{shell, toolchain};
+
+#[derive(Clap)]
+pub struct TestOpts {
+    /// For conditional sly.json evaluation.
+    #[clap(long, default_value = "default")]
+    with_mode: String,
+    /// Start a new replica with an empty state, deploy the canisters to it and run the tests
+    /// against it.
+    #[clap(long)]
+    fresh_replica: bool,
+    /// The canisters to test, all of the canisters with a test command are tested if empty.
+    canisters: Vec<String>,
+}
+
+#[async_trait]
+impl AsyncCommand for TestOpts {
+    async fn async_exec(self, env: &mut Env) -> Result<()> {
+        let workspace = env.workspace()?;
+
+        for name in &self.canisters {
+            workspace
+                .get_canister(name)
+                .ok_or_else(|| anyhow!("Canister '{}' not found.", name))?;
+        }
+
+        let canisters: Vec<String> = if self.canisters.is_empty() {
+            workspace
+                .canisters
+                .iter()
+                .filter(|(_, c)| !c.test.is_empty())
+                .map(|(name, _)| name.clone())
+                .collect()
+        } else {
+            self.canisters.clone()
+        };
+
+        let canisters = workspace.topological_order(&canisters)?;
+
+        for name in &canisters {
+            let test = &workspace.get_canister(name).unwrap().test;
+            if !test.contains_key(&self.with_mode) {
+                let modes = test.keys().cloned().collect::<Vec<_>>();
+                bail!(
+                    "Canister '{}' does not have a test command for mode '{}'. Use --with-mode=[{}]",
+                    name,
+                    self.with_mode,
+                    modes.join("/")
+                );
+            }
+        }
+
+        if canisters.is_empty() {
+            println!("No canister has a test command.");
+            return Ok(());
+        }
+
+        if !self.fresh_replica {
+            return self.run_tests(env, &canisters);
+        }
+
+        let state_dir = workspace.sly_dir().join("test-replica");
+        let replica = IsolatedReplica::start(&state_dir).await?;
+        env.use_isolated_replica(replica.url.clone(), state_dir);
+
+        let result = self.deploy_and_test(env, &canisters).await;
+        replica.stop().await;
+        result
+    }
+}
+
+impl TestOpts {
+    async fn deploy_and_test(&self, env: &mut Env, canisters: &[String]) -> Result<()> {
+        let deploy_opts = DeployOpts {
+            mode: "install".to_string(),
+            with_mode: self.with_mode.clone(),
+            all: false,
+            force: false,
+            allow_breaking: false,
+            plan: false,
+            jobs: None,
+            no_cache: false,
+            canisters: canisters.to_vec(),
+        };
+
+        deploy_opts
+            .async_exec(env)
+            .await
+            .context("Failed to deploy the canisters to the test replica.")?;
+
+        self.run_tests(env, canisters)
+    }
+
+    /// Run the test commands of every canister, a failing canister does not stop the others.
+    fn run_tests(&self, env: &Env, canisters: &[String]) -> Result<()> {
+        let workspace = env.workspace()?;
+        let mut results = Vec::new();
+
+        for name in canisters {
+            let canister = workspace.get_canister(name).unwrap();
+            let commands = &canister.test[&self.with_mode];
+            let envs = env.command_envs(name, &self.with_mode)?;
+            let prefix = format!("[{}]", name);
+            let start = Instant::now();
+
+            let result = commands.iter().try_for_each(|command| {
+                shell::run_prefixed(command, &workspace.root, envs.iter().cloned(), &prefix)
+            });
+
+            results.push((name, result, start.elapsed()));
+        }
+
+        println!();
+        let mut failed = 0;
+        for (name, result, elapsed) in &results {
+            match result {
+                Ok(()) => println!("PASS {} ({:.2}s)", name, elapsed.as_secs_f64()),
+                Err(e) => {
+                    failed += 1;
+                    println!("FAIL {} ({:.2}s): {}", name, elapsed.as_secs_f64(), e);
+                }
+            }
+        }
+
+        if failed > 0 {
+            bail!(
+                "{} of {} canisters failed the tests.",
+                failed,
+                results.len()
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// A replica with its own state that is started for the tests.
+struct IsolatedReplica {
+    url: String,
+    root: PathBuf,
+    shutdown_controller: Addr<ShutdownController>,
+}
+
+impl IsolatedReplica {
+    /// Start a replica whose state lives in the given directory, any previous state is removed.
+    async fn start(state_dir: &Path) -> Result<Self> {
+        if state_dir.exists() {
+            fs::remove_dir_all(state_dir)
+                .with_context(|| format!("Could not remove {:?}", state_dir))?;
+        }
+
+        let root = state_dir.join("replica");
+        let shutdown_controller = start_shutdown_controller()?;
+        start_replica(Some(shutdown_controller.clone()), true, &root)?;
+
+        let port_file = toolchain::get_replica_port_file(&root);
+        let deadline = Instant::now() + Duration::from_secs(30);
+
+        loop {
+            if let Ok(port) = fs::read_to_string(&port_file)
+                .unwrap_or_default()
+                .trim()
+                .parse::<u16>()
+            {
+                return Ok(Self {
+                    url: format!("http://localhost:{}", port),
+                    root,
+                    shutdown_controller,
+                });
+            }
+
+            if Instant::now() > deadline {
+                bail!("Timed out waiting for the test replica to start.");
+            }
+
+            actix::clock::sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    /// Stop the replica and wait for the process to exit.
+    async fn stop(self) {
+        self.shutdown_controller.do_send(ShutdownTrigger());
+
+        let pid_file = toolchain::get_replica_pid_file(&self.root);
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while pid_file.exists() && Instant::now() < deadline {
+            actix::clock::sleep(Duration::from_millis(100)).await;
+        }
+    }
+}
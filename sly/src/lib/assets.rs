@@ -0,0 +1,471 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::ops::Range;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use candid::{CandidType, Decode, Deserialize, Encode, Nat, Principal};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use ic_agent::Agent;
+use walkdir::WalkDir;
+
+use crate::commands::call::waiter;
+use crate::lib::{utils, wasm};
+
+/// The maximum size of a chunk we upload in one message, slightly less than the ingress
+/// message limit to leave room for the envelope.
+pub const MAX_CHUNK_SIZE: usize = 1_900_000;
+
+/// The maximum size of the operations that are committed in one commit_batch message.
+const MAX_COMMIT_SIZE: usize = wasm::MAX_INGRESS_MESSAGE_SIZE - wasm::ENVELOPE_OVERHEAD;
+
+/// A file that should be served by the assets canister.
+struct LocalAsset {
+    content_type: String,
+    /// The content of the asset in each encoding.
+    encodings: BTreeMap<String, Vec<u8>>,
+}
+
+/// What a sync did to the assets canister.
+#[derive(Default)]
+pub struct SyncSummary {
+    pub uploaded: usize,
+    pub unchanged: usize,
+    pub deleted: usize,
+}
+
+#[derive(CandidType, Deserialize)]
+struct ListRequest {}
+
+#[derive(CandidType, Deserialize)]
+struct AssetEncodingDetails {
+    content_encoding: String,
+    sha256: Option<Vec<u8>>,
+}
+
+#[derive(CandidType, Deserialize)]
+struct AssetDetails {
+    key: String,
+    content_type: String,
+    encodings: Vec<AssetEncodingDetails>,
+}
+
+#[derive(CandidType, Deserialize)]
+struct CreateBatchRequest {}
+
+#[derive(CandidType, Deserialize)]
+struct CreateBatchResponse {
+    batch_id: Nat,
+}
+
+#[derive(CandidType, Deserialize)]
+struct CreateChunkRequest {
+    batch_id: Nat,
+    content: Vec<u8>,
+}
+
+#[derive(CandidType, Deserialize)]
+struct CreateChunkResponse {
+    chunk_id: Nat,
+}
+
+#[derive(CandidType, Deserialize)]
+struct CommitBatchRequest {
+    batch_id: Nat,
+    operations: Vec<BatchOperationKind>,
+}
+
+#[derive(CandidType, Deserialize)]
+struct CreateAssetArguments {
+    key: String,
+    content_type: String,
+}
+
+#[derive(CandidType, Deserialize)]
+struct SetAssetContentArguments {
+    key: String,
+    content_encoding: String,
+    chunk_ids: Vec<Nat>,
+    sha256: Option<Vec<u8>>,
+}
+
+#[derive(CandidType, Deserialize)]
+struct UnsetAssetContentArguments {
+    key: String,
+    content_encoding: String,
+}
+
+#[derive(CandidType, Deserialize)]
+struct DeleteAssetArguments {
+    key: String,
+}
+
+#[derive(CandidType, Deserialize)]
+enum BatchOperationKind {
+    CreateAsset(CreateAssetArguments),
+    SetAssetContent(SetAssetContentArguments),
+    UnsetAssetContent(UnsetAssetContentArguments),
+    DeleteAsset(DeleteAssetArguments),
+}
+
+/// A change to the assets canister, the content of an upload is only sent in the batch that
+/// commits it.
+enum Change<'a> {
+    Operation(BatchOperationKind),
+    Upload {
+        key: String,
+        encoding: String,
+        content: &'a [u8],
+        sha256: Vec<u8>,
+    },
+}
+
+impl Change<'_> {
+    /// Return an upper bound of the size of the operation in the commit_batch argument.
+    fn commit_size(&self) -> usize {
+        match self {
+            Change::Operation(operation) => Encode!(operation).map_or(0, |arg| arg.len()),
+            Change::Upload {
+                key,
+                encoding,
+                content,
+                sha256,
+            } => {
+                let chunks = (content.len() + MAX_CHUNK_SIZE - 1) / MAX_CHUNK_SIZE;
+                // Each chunk id is a nat of at most 10 bytes, plus the tags of the record.
+                key.len() + encoding.len() + sha256.len() + chunks * 10 + 64
+            }
+        }
+    }
+}
+
+/// Upload the files in the given source directories to the assets canister, only the files
+/// that are changed are uploaded and the assets that no longer exist are deleted.
+pub async fn sync(
+    agent: &Agent,
+    canister_id: Principal,
+    root: &Path,
+    sources: &[String],
+) -> Result<SyncSummary> {
+    let local = collect(root, sources)?;
+    let remote = list(agent, canister_id)
+        .await?
+        .into_iter()
+        .map(|asset| (asset.key.clone(), asset))
+        .collect::<BTreeMap<_, _>>();
+
+    let mut summary = SyncSummary::default();
+    let mut operations = Vec::new();
+    // The contents we need to upload: (key, encoding, content).
+    let mut uploads = Vec::new();
+
+    for key in remote.keys() {
+        if !local.contains_key(key) {
+            log::debug!("Deleting stale asset {}", key);
+            operations.push(BatchOperationKind::DeleteAsset(DeleteAssetArguments {
+                key: key.clone(),
+            }));
+            summary.deleted += 1;
+        }
+    }
+
+    for (key, asset) in &local {
+        let existing = match remote.get(key) {
+            Some(existing) if existing.content_type == asset.content_type => Some(existing),
+            Some(_) => {
+                operations.push(BatchOperationKind::DeleteAsset(DeleteAssetArguments {
+                    key: key.clone(),
+                }));
+                None
+            }
+            None => None,
+        };
+
+        if existing.is_none() {
+            operations.push(BatchOperationKind::CreateAsset(CreateAssetArguments {
+                key: key.clone(),
+                content_type: asset.content_type.clone(),
+            }));
+        }
+
+        let mut changed = existing.is_none();
+
+        for (encoding, content) in &asset.encodings {
+            let sha256 = utils::sha256(content);
+            let is_same = existing.map_or(false, |existing| {
+                existing
+                    .encodings
+                    .iter()
+                    .any(|e| &e.content_encoding == encoding && e.sha256.as_ref() == Some(&sha256))
+            });
+
+            if !is_same {
+                uploads.push((key.clone(), encoding.clone(), content, sha256));
+                changed = true;
+            }
+        }
+
+        if let Some(existing) = existing {
+            for encoding in &existing.encodings {
+                if !asset.encodings.contains_key(&encoding.content_encoding) {
+                    operations.push(BatchOperationKind::UnsetAssetContent(
+                        UnsetAssetContentArguments {
+                            key: key.clone(),
+                            content_encoding: encoding.content_encoding.clone(),
+                        },
+                    ));
+                    changed = true;
+                }
+            }
+        }
+
+        if changed {
+            summary.uploaded += 1;
+        } else {
+            summary.unchanged += 1;
+        }
+    }
+
+    let mut changes = operations
+        .into_iter()
+        .map(Change::Operation)
+        .collect::<Vec<_>>();
+    changes.extend(
+        uploads
+            .into_iter()
+            .map(|(key, encoding, content, sha256)| Change::Upload {
+                key,
+                encoding,
+                content,
+                sha256,
+            }),
+    );
+
+    // A single commit_batch message can not hold the operations of a large directory, so
+    // they are committed over several batches, in order.
+    let sizes = changes.iter().map(|c| c.commit_size()).collect::<Vec<_>>();
+    let groups = split_by_size(&sizes, MAX_COMMIT_SIZE);
+    let mut changes = changes.into_iter();
+
+    for group in groups {
+        let batch_id = create_batch(agent, canister_id).await?;
+        let mut operations = Vec::with_capacity(group.len());
+
+        for change in changes.by_ref().take(group.len()) {
+            let (key, encoding, content, sha256) = match change {
+                Change::Operation(operation) => {
+                    operations.push(operation);
+                    continue;
+                }
+                Change::Upload {
+                    key,
+                    encoding,
+                    content,
+                    sha256,
+                } => (key, encoding, content, sha256),
+            };
+
+            log::debug!("Uploading {} ({})", key, encoding);
+
+            let mut chunk_ids = Vec::new();
+            for chunk in content.chunks(MAX_CHUNK_SIZE) {
+                chunk_ids.push(create_chunk(agent, canister_id, &batch_id, chunk).await?);
+            }
+
+            operations.push(BatchOperationKind::SetAssetContent(
+                SetAssetContentArguments {
+                    key,
+                    content_encoding: encoding,
+                    chunk_ids,
+                    sha256: Some(sha256),
+                },
+            ));
+        }
+
+        commit_batch(agent, canister_id, batch_id, operations).await?;
+    }
+
+    Ok(summary)
+}
+
+/// Split a list of items into consecutive groups whose total size is at most `limit`, an item
+/// that is larger than the limit is put in a group of its own.
+fn split_by_size(sizes: &[usize], limit: usize) -> Vec<Range<usize>> {
+    let mut groups = Vec::new();
+    let mut start = 0;
+    let mut total = 0;
+
+    for (index, size) in sizes.iter().enumerate() {
+        if index > start && total + size > limit {
+            groups.push(start..index);
+            start = index;
+            total = 0;
+        }
+        total += size;
+    }
+
+    if start < sizes.len() {
+        groups.push(start..sizes.len());
+    }
+
+    groups
+}
+
+/// Read all of the files in the source directories, the key of each asset is its path
+/// relative to the source directory it is in.
+fn collect(root: &Path, sources: &[String]) -> Result<BTreeMap<String, LocalAsset>> {
+    let mut result = BTreeMap::new();
+
+    for source in sources {
+        let dir = root.join(source);
+        if !dir.is_dir() {
+            bail!("Asset source {:?} is not a directory.", dir);
+        }
+
+        for entry in WalkDir::new(&dir).follow_links(true) {
+            let entry = entry.with_context(|| format!("Failed to read {:?}", dir))?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+
+            let path = entry.path();
+            let relative = path.strip_prefix(&dir).unwrap();
+            let key = format!(
+                "/{}",
+                relative
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/")
+            );
+
+            if result.contains_key(&key) {
+                bail!("Asset {} exists in more than one source directory.", key);
+            }
+
+            let content = fs::read(path).with_context(|| format!("Could not read {:?}", path))?;
+            let content_type = mime_guess::from_path(path)
+                .first_or_octet_stream()
+                .to_string();
+
+            let mut encodings = BTreeMap::new();
+            if should_gzip(&content_type) {
+                let gzipped = gzip(&content)?;
+                if gzipped.len() < content.len() {
+                    encodings.insert("gzip".to_string(), gzipped);
+                }
+            }
+            encodings.insert("identity".to_string(), content);
+
+            result.insert(
+                key,
+                LocalAsset {
+                    content_type,
+                    encodings,
+                },
+            );
+        }
+    }
+
+    Ok(result)
+}
+
+/// Returns true for the content types that benefit from compression.
+fn should_gzip(content_type: &str) -> bool {
+    content_type.starts_with("text/")
+        || matches!(
+            content_type,
+            "application/javascript" | "application/json" | "image/svg+xml" | "application/wasm"
+        )
+}
+
+fn gzip(content: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+    encoder
+        .write_all(content)
+        .context("Failed to gzip an asset.")?;
+    encoder.finish().context("Failed to gzip an asset.")
+}
+
+async fn list(agent: &Agent, canister_id: Principal) -> Result<Vec<AssetDetails>> {
+    let response = agent
+        .query(&canister_id, "list")
+        .with_arg(Encode!(&ListRequest {})?)
+        .call()
+        .await
+        .context("list call failed.")?;
+
+    Decode!(&response, Vec<AssetDetails>).context("Failed to decode the list response.")
+}
+
+async fn create_batch(agent: &Agent, canister_id: Principal) -> Result<Nat> {
+    let response = agent
+        .update(&canister_id, "create_batch")
+        .with_arg(Encode!(&CreateBatchRequest {})?)
+        .call_and_wait(waiter::waiter_with_exponential_backoff())
+        .await
+        .context("create_batch call failed.")?;
+
+    Ok(Decode!(&response, CreateBatchResponse)
+        .context("Failed to decode the create_batch response.")?
+        .batch_id)
+}
+
+async fn create_chunk(
+    agent: &Agent,
+    canister_id: Principal,
+    batch_id: &Nat,
+    content: &[u8],
+) -> Result<Nat> {
+    let arg = Encode!(&CreateChunkRequest {
+        batch_id: batch_id.clone(),
+        content: content.to_vec(),
+    })?;
+
+    let response = agent
+        .update(&canister_id, "create_chunk")
+        .with_arg(arg)
+        .call_and_wait(waiter::waiter_with_exponential_backoff())
+        .await
+        .context("create_chunk call failed.")?;
+
+    Ok(Decode!(&response, CreateChunkResponse)
+        .context("Failed to decode the create_chunk response.")?
+        .chunk_id)
+}
+
+async fn commit_batch(
+    agent: &Agent,
+    canister_id: Principal,
+    batch_id: Nat,
+    operations: Vec<BatchOperationKind>,
+) -> Result<()> {
+    let arg = Encode!(&CommitBatchRequest {
+        batch_id,
+        operations,
+    })?;
+
+    agent
+        .update(&canister_id, "commit_batch")
+        .with_arg(arg)
+        .call_and_wait(waiter::waiter_with_exponential_backoff())
+        .await
+        .context("commit_batch call failed.")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_operations_by_size() {
+        assert!(split_by_size(&[], 10).is_empty());
+        assert_eq!(split_by_size(&[3, 3, 3], 10), vec![0..3]);
+        assert_eq!(split_by_size(&[4, 4, 4, 4], 10), vec![0..2, 2..4]);
+        assert_eq!(split_by_size(&[2, 12, 2], 10), vec![0..1, 1..2, 2..3]);
+    }
+}
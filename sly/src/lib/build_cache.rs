@@ -0,0 +1,310 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use ring::digest::{Context as DigestContext, SHA256};
+use serde::{Deserialize, Serialize};
+
+use crate::lib::env::canister_id_env_var;
+use crate::lib::utils;
+use crate::lib::workspace::OptimizeSettings;
+
+/// The information stored about the last build of a canister in a certain mode.
+#[derive(Serialize, Deserialize, PartialEq)]
+struct Entry {
+    /// Hash of the inputs, the build commands and the toolchain.
+    key: String,
+    /// Hex encoded sha256 of the wasm that the build produced.
+    output_sha256: String,
+}
+
+/// Keeps track of the inputs of the previous builds under `.sly/cache`, so that builds of
+/// canisters whose inputs did not change can be skipped. The entries are stored per network
+/// in `.sly/cache/<network>/<canister>/<mode>.json`.
+pub struct BuildCache {
+    dir: PathBuf,
+    network: String,
+}
+
+/// The things that determine the output of a build.
+pub struct BuildInputs<'a> {
+    /// The root of the workspace, the globs are relative to this directory.
+    pub root: &'a Path,
+    /// The glob patterns of the input files.
+    pub globs: &'a [String],
+    /// The build commands.
+    pub commands: &'a [String],
+    /// The environment variables passed to the build commands.
+    pub envs: &'a [(String, String)],
+    /// The dependencies of the canister, only the ids of these canisters are part of the key.
+    pub dependencies: &'a [String],
+    /// The version of the toolchain used to build the canister.
+    pub toolchain: &'a str,
+    /// The optimization that runs on the wasm after the build, `None` if it is disabled.
+    pub optimize: Option<&'a OptimizeSettings>,
+}
+
+impl BuildCache {
+    /// Create the cache of the given network in the given `.sly` directory.
+    pub fn new(sly_dir: &Path, network: &str) -> Self {
+        Self {
+            dir: sly_dir.join("cache"),
+            network: network.to_owned(),
+        }
+    }
+
+    /// Return the directory the cache of every network is stored in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Compute the key of a build, returns `None` if the build does not declare any inputs
+    /// in which case it can not be cached.
+    pub fn compute_key(inputs: &BuildInputs) -> Result<Option<String>> {
+        if inputs.globs.is_empty() {
+            return Ok(None);
+        }
+
+        let mut files = Vec::new();
+        for pattern in inputs.globs {
+            let full_pattern = inputs.root.join(pattern);
+            let paths = glob::glob(&full_pattern.to_string_lossy())
+                .with_context(|| format!("Invalid input pattern '{}'", pattern))?;
+
+            for path in paths {
+                let path = path.context("Failed to read the input files.")?;
+                if path.is_file() {
+                    files.push(path);
+                }
+            }
+        }
+
+        files.sort();
+        files.dedup();
+
+        let mut context = DigestContext::new(&SHA256);
+        context.update(env!("CARGO_PKG_VERSION").as_bytes());
+        context.update(inputs.toolchain.as_bytes());
+
+        for command in inputs.commands {
+            context.update(command.as_bytes());
+            context.update(&[0]);
+        }
+
+        match inputs.optimize {
+            Some(settings) => {
+                let passes = match &settings.passes {
+                    Some(passes) => passes.join(","),
+                    None => "all".to_string(),
+                };
+                context.update(format!("optimize:{}:{}", passes, settings.level).as_bytes());
+            }
+            None => context.update(b"optimize:disabled"),
+        }
+        context.update(&[0]);
+
+        // Creating an unrelated canister should not invalidate the build.
+        let dependency_vars = inputs
+            .dependencies
+            .iter()
+            .map(|name| canister_id_env_var(name))
+            .collect::<Vec<_>>();
+        let envs = inputs.envs.iter().filter(|(key, _)| {
+            !key.starts_with("SLY_CANISTER_ID_") || dependency_vars.contains(key)
+        });
+
+        for (key, value) in envs {
+            context.update(key.as_bytes());
+            context.update(&[0]);
+            context.update(value.as_bytes());
+            context.update(&[0]);
+        }
+
+        for file in files {
+            let content = fs::read(&file).with_context(|| format!("Could not read {:?}", file))?;
+            let relative = file.strip_prefix(inputs.root).unwrap_or(&file);
+            context.update(relative.to_string_lossy().as_bytes());
+            context.update(&[0]);
+            context.update(&utils::sha256(&content));
+        }
+
+        Ok(Some(hex::encode(context.finish())))
+    }
+
+    /// Returns true if the last build of the canister used the same key and its output is
+    /// still untouched.
+    pub fn is_fresh(&self, canister: &str, mode: &str, key: &str, output: &Path) -> bool {
+        let entry = match self.read(canister, mode) {
+            Some(entry) => entry,
+            None => return false,
+        };
+
+        if entry.key != key {
+            return false;
+        }
+
+        match fs::read(output) {
+            Ok(wasm) => hex::encode(utils::sha256(&wasm)) == entry.output_sha256,
+            Err(_) => false,
+        }
+    }
+
+    /// Store the key of a successful build along with the hash of its output.
+    pub fn store(&self, canister: &str, mode: &str, key: &str, output: &Path) -> Result<()> {
+        let wasm = fs::read(output).with_context(|| format!("Could not read {:?}", output))?;
+        let entry = Entry {
+            key: key.to_owned(),
+            output_sha256: hex::encode(utils::sha256(&wasm)),
+        };
+
+        let path = self.entry_path(canister, mode);
+        let dir = path.parent().unwrap();
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+
+        let json = serde_json::to_string_pretty(&entry)?;
+        utils::write_atomic(&path, json.as_bytes())
+    }
+
+    /// Remove all of the cached entries, of every network.
+    pub fn clear(&self) -> Result<()> {
+        if self.dir.is_dir() {
+            fs::remove_dir_all(&self.dir)
+                .with_context(|| format!("Could not remove {:?}", self.dir))?;
+        }
+        Ok(())
+    }
+
+    fn read(&self, canister: &str, mode: &str) -> Option<Entry> {
+        let json = fs::read_to_string(self.entry_path(canister, mode)).ok()?;
+        serde_json::from_str(&json).ok()
+    }
+
+    fn entry_path(&self, canister: &str, mode: &str) -> PathBuf {
+        self.dir
+            .join(escape(&self.network))
+            .join(escape(canister))
+            .join(format!("{}.json", escape(mode)))
+    }
+}
+
+/// Escape a name so it can be used as a path component, the escaping is reversible so two
+/// different names never share a file.
+fn escape(name: &str) -> String {
+    let mut result = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' => result.push(byte as char),
+            _ => result.push_str(&format!("%{:02x}", byte)),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sly-{}-{}", name, std::process::id()));
+        fs::create_dir_all(dir.join("src")).unwrap();
+        dir
+    }
+
+    fn key(root: &Path, envs: &[(String, String)], optimize: Option<&OptimizeSettings>) -> String {
+        let globs = vec!["src/*.rs".to_string()];
+        let commands = vec!["cargo build".to_string()];
+        let dependencies = vec!["ledger".to_string()];
+
+        BuildCache::compute_key(&BuildInputs {
+            root,
+            globs: &globs,
+            commands: &commands,
+            envs,
+            dependencies: &dependencies,
+            toolchain: "1.0",
+            optimize,
+        })
+        .unwrap()
+        .unwrap()
+    }
+
+    #[test]
+    fn compute_key() {
+        let root = temp_dir("cache-key");
+        fs::write(root.join("src/lib.rs"), "fn a() {}").unwrap();
+
+        let envs = vec![("SLY_MODE".to_string(), "default".to_string())];
+        let base = key(&root, &envs, None);
+        assert_eq!(base, key(&root, &envs, None));
+
+        // The ids of canisters that are not dependencies are ignored.
+        let mut with_other = envs.clone();
+        with_other.push(("SLY_CANISTER_ID_OTHER".into(), "aaaaa-aa".into()));
+        assert_eq!(base, key(&root, &with_other, None));
+
+        let mut with_dependency = envs.clone();
+        with_dependency.push(("SLY_CANISTER_ID_LEDGER".into(), "aaaaa-aa".into()));
+        assert_ne!(base, key(&root, &with_dependency, None));
+
+        let settings = OptimizeSettings {
+            passes: None,
+            level: 2,
+        };
+        let optimized = key(&root, &envs, Some(&settings));
+        assert_ne!(base, optimized);
+
+        let settings = OptimizeSettings {
+            passes: None,
+            level: 3,
+        };
+        assert_ne!(optimized, key(&root, &envs, Some(&settings)));
+
+        fs::write(root.join("src/lib.rs"), "fn b() {}").unwrap();
+        assert_ne!(base, key(&root, &envs, None));
+
+        let inputs = BuildInputs {
+            root: &root,
+            globs: &[],
+            commands: &[],
+            envs: &[],
+            dependencies: &[],
+            toolchain: "1.0",
+            optimize: None,
+        };
+        assert!(BuildCache::compute_key(&inputs).unwrap().is_none());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn is_fresh_and_store() {
+        let root = temp_dir("cache-store");
+        let cache = BuildCache::new(&root.join(".sly"), "local");
+        let output = root.join("a.wasm");
+        fs::write(&output, b"wasm").unwrap();
+
+        assert!(!cache.is_fresh("a", "default", "key", &output));
+
+        cache.store("a", "default", "key", &output).unwrap();
+        assert!(cache.is_fresh("a", "default", "key", &output));
+        assert!(!cache.is_fresh("a", "default", "other", &output));
+        assert!(!cache.is_fresh("a", "release", "key", &output));
+
+        // The names can not collide, and every network has its own entries.
+        let other = BuildCache::new(&root.join(".sly"), "ic");
+        assert!(!other.is_fresh("a", "default", "key", &output));
+        assert!(!cache.is_fresh("a-default", "", "key", &output));
+        cache.store("a-b", "c", "key", &output).unwrap();
+        assert!(!cache.is_fresh("a", "b-c", "key", &output));
+        assert_ne!(cache.entry_path("a.b", "c"), cache.entry_path("a%2eb", "c"));
+
+        // A modified output is rebuilt.
+        fs::write(&output, b"modified").unwrap();
+        assert!(!cache.is_fresh("a", "default", "key", &output));
+
+        cache.clear().unwrap();
+        assert!(!cache.dir().exists());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,355 @@
+use std::collections::BTreeSet;
+
+use anyhow::{bail, Context, Result};
+use candid::parser::typing::check_prog;
+use candid::types::{Field, Function, Type};
+use candid::{IDLProg, TypeEnv};
+
+/// A candid service along with the type env it is defined in.
+pub struct Service {
+    pub env: TypeEnv,
+    pub actor: Type,
+}
+
+/// A change in a method of a service that breaks the existing clients.
+pub struct BreakingChange {
+    /// Name of the method.
+    pub method: String,
+    /// Why the new method is not compatible with the old one.
+    pub reason: String,
+}
+
+impl Service {
+    /// Create a service from the type env and the actor that are obtained from
+    /// a candid file, fails if the file does not define a service.
+    pub fn new(env: TypeEnv, actor: Option<Type>) -> Result<Self> {
+        match actor {
+            Some(actor) => Ok(Self { env, actor }),
+            None => bail!("The candid file does not define a service."),
+        }
+    }
+
+    /// Parse a self-contained candid source, i.e one without any imports.
+    pub fn from_source(source: &str) -> Result<Self> {
+        let prog = source
+            .parse::<IDLProg>()
+            .context("Failed to parse the candid source.")?;
+        let mut env = TypeEnv::new();
+        let actor =
+            check_prog(&mut env, &prog).context("Failed to type check the candid source.")?;
+        Self::new(env, actor)
+    }
+
+    /// Return the source of a self-contained candid file for this service.
+    pub fn to_source(&self) -> String {
+        candid::bindings::candid::compile(&self.env, &Some(self.actor.clone()))
+    }
+}
+
+/// Check that clients of the `old` service can keep using the `new` one, i.e that
+/// the new service is a subtype of the old one, and return the methods that break
+/// this rule.
+pub fn find_breaking_changes(new: &Service, old: &Service) -> Vec<BreakingChange> {
+    let checker = SubtypeChecker {
+        new_env: &new.env,
+        old_env: &old.env,
+    };
+
+    let new_methods = match checker.service_methods(&new.actor, Side::New) {
+        Ok(methods) => methods,
+        Err(reason) => {
+            return vec![BreakingChange {
+                method: "<service>".into(),
+                reason,
+            }]
+        }
+    };
+
+    let old_methods = match checker.service_methods(&old.actor, Side::Old) {
+        Ok(methods) => methods,
+        Err(reason) => {
+            return vec![BreakingChange {
+                method: "<service>".into(),
+                reason,
+            }]
+        }
+    };
+
+    let mut result = Vec::new();
+
+    for (name, old_ty) in old_methods {
+        let new_ty = match new_methods.iter().find(|(n, _)| n == name) {
+            Some((_, ty)) => ty,
+            None => {
+                result.push(BreakingChange {
+                    method: name.clone(),
+                    reason: "The method was removed.".into(),
+                });
+                continue;
+            }
+        };
+
+        let mut visited = BTreeSet::new();
+        if let Err(reason) = checker.subtype(new_ty, old_ty, &mut visited) {
+            result.push(BreakingChange {
+                method: name.clone(),
+                reason,
+            });
+        }
+    }
+
+    result
+}
+
+#[derive(Copy, Clone)]
+enum Side {
+    New,
+    Old,
+}
+
+/// Implements the candid subtyping rules between two types that are defined in
+/// different type environments.
+struct SubtypeChecker<'a> {
+    new_env: &'a TypeEnv,
+    old_env: &'a TypeEnv,
+}
+
+impl<'a> SubtypeChecker<'a> {
+    fn env(&self, side: Side) -> &'a TypeEnv {
+        match side {
+            Side::New => self.new_env,
+            Side::Old => self.old_env,
+        }
+    }
+
+    /// Follow the type variables until we reach an actual type.
+    fn resolve(&self, mut ty: &'a Type, side: Side) -> Result<&'a Type, String> {
+        let env = self.env(side);
+        let mut visited = BTreeSet::new();
+
+        while let Type::Var(name) = ty {
+            if !visited.insert(name.clone()) {
+                return Err(format!("Type {} has circular definition.", name));
+            }
+
+            ty = env
+                .0
+                .get(name)
+                .ok_or_else(|| format!("Unbound type identifier: {}", name))?;
+        }
+
+        Ok(ty)
+    }
+
+    fn service_methods(&self, ty: &'a Type, side: Side) -> Result<&'a [(String, Type)], String> {
+        match self.resolve(ty, side)? {
+            Type::Service(methods) => Ok(methods),
+            Type::Class(_, ty) => self.service_methods(ty, side),
+            ty => Err(format!("Not a service type: {}", ty)),
+        }
+    }
+
+    /// Check that `sub` (defined in the new env) is a subtype of `sup` (defined in the
+    /// old env). Pairs of type variables that are already being checked are assumed to
+    /// be subtypes, which handles recursive types.
+    fn subtype(
+        &self,
+        sub: &'a Type,
+        sup: &'a Type,
+        visited: &mut BTreeSet<(bool, String, String)>,
+    ) -> Result<(), String> {
+        self.subtype_between(sub, Side::New, sup, Side::Old, visited)
+    }
+
+    fn subtype_between(
+        &self,
+        sub: &'a Type,
+        sub_side: Side,
+        sup: &'a Type,
+        sup_side: Side,
+        visited: &mut BTreeSet<(bool, String, String)>,
+    ) -> Result<(), String> {
+        if let (Type::Var(a), Type::Var(b)) = (sub, sup) {
+            let key = (matches!(sub_side, Side::New), a.clone(), b.clone());
+            if !visited.insert(key) {
+                return Ok(());
+            }
+        }
+
+        let sub_ty = self.resolve(sub, sub_side)?;
+        let sup_ty = self.resolve(sup, sup_side)?;
+
+        match (sub_ty, sup_ty) {
+            (_, Type::Reserved) => Ok(()),
+            (Type::Empty, _) => Ok(()),
+            (Type::Nat, Type::Int) => Ok(()),
+            // Any value can be decoded as an optional, it becomes null if it does not match.
+            (_, Type::Opt(_)) => Ok(()),
+            (Type::Vec(a), Type::Vec(b)) => self
+                .subtype_between(a, sub_side, b, sup_side, visited)
+                .map_err(|e| format!("In vector element: {}", e)),
+            (Type::Record(a), Type::Record(b)) => {
+                self.record_subtype(a, sub_side, b, sup_side, visited)
+            }
+            (Type::Variant(a), Type::Variant(b)) => {
+                for field in a {
+                    let other = b
+                        .iter()
+                        .find(|f| f.id.get_id() == field.id.get_id())
+                        .ok_or_else(|| format!("Variant case '{}' is not expected.", field.id))?;
+
+                    self.subtype_between(&field.ty, sub_side, &other.ty, sup_side, visited)
+                        .map_err(|e| format!("In variant case '{}': {}", field.id, e))?;
+                }
+
+                Ok(())
+            }
+            (Type::Func(a), Type::Func(b)) => self.func_subtype(a, sub_side, b, sup_side, visited),
+            (Type::Service(a), Type::Service(b)) => {
+                for (name, ty) in b {
+                    let (_, other) = a
+                        .iter()
+                        .find(|(n, _)| n == name)
+                        .ok_or_else(|| format!("Method '{}' is missing.", name))?;
+
+                    self.subtype_between(other, sub_side, ty, sup_side, visited)
+                        .map_err(|e| format!("In method '{}': {}", name, e))?;
+                }
+
+                Ok(())
+            }
+            (Type::Class(_, a), Type::Class(_, b)) => {
+                self.subtype_between(a, sub_side, b, sup_side, visited)
+            }
+            (a, b) if a == b && is_primitive(a) => Ok(()),
+            (a, b) => Err(format!("Type {} is not compatible with {}.", a, b)),
+        }
+    }
+
+    fn record_subtype(
+        &self,
+        sub: &'a [Field],
+        sub_side: Side,
+        sup: &'a [Field],
+        sup_side: Side,
+        visited: &mut BTreeSet<(bool, String, String)>,
+    ) -> Result<(), String> {
+        for field in sup {
+            match sub.iter().find(|f| f.id.get_id() == field.id.get_id()) {
+                Some(other) => self
+                    .subtype_between(&other.ty, sub_side, &field.ty, sup_side, visited)
+                    .map_err(|e| format!("In record field '{}': {}", field.id, e))?,
+                None if self.is_optional(&field.ty, sup_side)? => {}
+                None => return Err(format!("Record field '{}' is missing.", field.id)),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Functions are contravariant in their arguments and covariant in their results.
+    fn func_subtype(
+        &self,
+        sub: &'a Function,
+        sub_side: Side,
+        sup: &'a Function,
+        sup_side: Side,
+        visited: &mut BTreeSet<(bool, String, String)>,
+    ) -> Result<(), String> {
+        if sub.modes != sup.modes {
+            return Err("The function modes (query/oneway) are changed.".into());
+        }
+
+        self.tuple_subtype(&sup.args, sup_side, &sub.args, sub_side, visited)
+            .map_err(|e| format!("In arguments: {}", e))?;
+
+        self.tuple_subtype(&sub.rets, sub_side, &sup.rets, sup_side, visited)
+            .map_err(|e| format!("In results: {}", e))
+    }
+
+    /// Argument and result sequences can be extended with optional values.
+    fn tuple_subtype(
+        &self,
+        sub: &'a [Type],
+        sub_side: Side,
+        sup: &'a [Type],
+        sup_side: Side,
+        visited: &mut BTreeSet<(bool, String, String)>,
+    ) -> Result<(), String> {
+        for (i, ty) in sup.iter().enumerate() {
+            match sub.get(i) {
+                Some(other) => self
+                    .subtype_between(other, sub_side, ty, sup_side, visited)
+                    .map_err(|e| format!("At position {}: {}", i, e))?,
+                None if self.is_optional(ty, sup_side)? => {}
+                None => return Err(format!("Value at position {} is missing.", i)),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn is_optional(&self, ty: &'a Type, side: Side) -> Result<bool, String> {
+        Ok(matches!(
+            self.resolve(ty, side)?,
+            Type::Opt(_) | Type::Null | Type::Reserved
+        ))
+    }
+}
+
+fn is_primitive(ty: &Type) -> bool {
+    matches!(
+        ty,
+        Type::Null
+            | Type::Bool
+            | Type::Nat
+            | Type::Int
+            | Type::Nat8
+            | Type::Nat16
+            | Type::Nat32
+            | Type::Nat64
+            | Type::Int8
+            | Type::Int16
+            | Type::Int32
+            | Type::Int64
+            | Type::Float32
+            | Type::Float64
+            | Type::Text
+            | Type::Principal
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(new: &str, old: &str) -> Vec<String> {
+        let new = Service::from_source(new).unwrap();
+        let old = Service::from_source(old).unwrap();
+        find_breaking_changes(&new, &old)
+            .into_iter()
+            .map(|c| c.method)
+            .collect()
+    }
+
+    #[test]
+    fn compatible_changes() {
+        let old = "service : { get : () -> (nat) query; set : (nat) -> () }";
+        let new = "service : { get : () -> (nat, opt text) query; set : (nat, opt nat) -> (); inc : () -> () }";
+        assert!(check(new, old).is_empty());
+    }
+
+    #[test]
+    fn breaking_changes() {
+        let old = "service : { get : () -> (nat) query; set : (nat) -> (); reset : () -> () }";
+        let new = "service : { get : () -> (text) query; set : (nat, nat) -> () }";
+        assert_eq!(check(new, old), vec!["get", "reset", "set"]);
+    }
+
+    #[test]
+    fn recursive_types() {
+        let old = "type List = opt record { nat; List }; service : { get : () -> (List) }";
+        let new = "type L = opt record { nat; L }; service : { get : () -> (L) }";
+        assert!(check(new, old).is_empty());
+    }
+}
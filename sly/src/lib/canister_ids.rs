@@ -0,0 +1,151 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use candid::Principal;
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+
+use crate::lib::utils;
+
+/// The content of a canister ids file, maps the name of each canister to its id on
+/// every network.
+#[derive(Serialize, Deserialize, Default)]
+struct CanisterIdJson(BTreeMap<String, BTreeMap<String, Principal>>);
+
+/// Access to the canister ids of a workspace on a certain network.
+///
+/// All of the accesses to the underlying file are guarded by an advisory lock, and the
+/// writes are atomic, so concurrent sly processes never observe a half-written file.
+pub struct CanisterIdStore {
+    /// Path to the json file.
+    path: PathBuf,
+    /// Name of the network the ids are stored for.
+    network: String,
+}
+
+impl CanisterIdStore {
+    /// Create a store for the canister ids of the workspace located at `root`.
+    pub fn new(root: &Path, network: &str, is_ephemeral: bool) -> Self {
+        // Use a different file for ephemeral networks so people can gitignore it.
+        let filename = if is_ephemeral {
+            format!("canister_ids-{}.json", utils::network_file_name(network))
+        } else {
+            "canister_ids.json".to_string()
+        };
+
+        Self {
+            path: root.join(filename),
+            network: network.to_owned(),
+        }
+    }
+
+    /// Return the path to the json file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Return the id of the given canister on the current network.
+    pub fn get(&self, canister: &str) -> Result<Option<Principal>> {
+        Ok(self.get_all()?.remove(canister))
+    }
+
+    /// Return the id of every canister that exists on the current network.
+    pub fn get_all(&self) -> Result<BTreeMap<String, Principal>> {
+        let lock = self.lock()?;
+        lock.lock_shared()
+            .with_context(|| format!("Failed to lock {}", self.display_name()))?;
+
+        let json = self.read()?;
+        Ok(json
+            .0
+            .into_iter()
+            .filter_map(|(name, mut ids)| Some((name, ids.remove(&self.network)?)))
+            .collect())
+    }
+
+    /// Store the id of a canister on the current network.
+    pub fn set(&self, canister: &str, canister_id: Principal) -> Result<()> {
+        self.update(|ids| {
+            ids.insert(canister.to_owned(), canister_id);
+        })
+    }
+
+    /// Run the given closure on the ids of the current network while holding an exclusive
+    /// lock, and persist the result.
+    pub fn update<F, R>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut BTreeMap<String, Principal>) -> R,
+    {
+        let lock = self.lock()?;
+        lock.lock_exclusive()
+            .with_context(|| format!("Failed to lock {}", self.display_name()))?;
+
+        let mut json = self.read()?;
+        let mut ids = json
+            .0
+            .iter()
+            .filter_map(|(name, ids)| Some((name.clone(), *ids.get(&self.network)?)))
+            .collect::<BTreeMap<_, _>>();
+
+        let result = f(&mut ids);
+
+        // Drop the ids that were removed by the closure.
+        for network_ids in json.0.values_mut() {
+            network_ids.remove(&self.network);
+        }
+
+        for (name, canister_id) in ids {
+            json.0
+                .entry(name)
+                .or_default()
+                .insert(self.network.clone(), canister_id);
+        }
+
+        json.0.retain(|_, ids| !ids.is_empty());
+
+        self.write(&json)?;
+
+        Ok(result)
+    }
+
+    fn read(&self) -> Result<CanisterIdJson> {
+        if !self.path.is_file() {
+            return Ok(CanisterIdJson::default());
+        }
+
+        let json = fs::read_to_string(&self.path)
+            .with_context(|| format!("Failed to read {}", self.display_name()))?;
+
+        if json.trim().is_empty() {
+            return Ok(CanisterIdJson::default());
+        }
+
+        serde_json::from_str(&json)
+            .with_context(|| format!("Failed to parse {}", self.display_name()))
+    }
+
+    fn write(&self, json: &CanisterIdJson) -> Result<()> {
+        let content =
+            serde_json::to_string_pretty(json).context("Failed to serialize canister ids.")?;
+        utils::write_atomic(&self.path, content.as_bytes())
+    }
+
+    /// Open the lock file of this store, the lock is released once the file is dropped.
+    fn lock(&self) -> Result<fs::File> {
+        let lock_path = self.path.with_extension("json.lock");
+        fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .open(&lock_path)
+            .with_context(|| format!("Failed to open {:?}", lock_path))
+    }
+
+    fn display_name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,166 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use candid::Principal;
+use fs2::FileExt;
+use serde::{Deserialize, Serialize};
+
+use crate::lib::utils;
+
+/// A successful install of a canister.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Deployment {
+    /// Name of the canister.
+    pub canister: String,
+    /// The install mode that was used.
+    pub mode: String,
+    /// Hex encoded sha256 of the installed module.
+    pub wasm_sha256: String,
+    /// The git commit of the workspace at the time of the install, if available.
+    pub git_commit: Option<String>,
+    /// The principal of the identity that installed the code.
+    pub identity: Principal,
+    /// Seconds since the unix epoch.
+    pub timestamp: u64,
+}
+
+/// The deployment history of a workspace on a certain network, stored under
+/// `.sly/deployments/<network>.json` along with a copy of every deployed wasm.
+///
+/// Like the canister ids, the json file is guarded by an advisory lock so concurrent deploys
+/// do not lose records.
+pub struct DeploymentHistory {
+    /// Path to the json file.
+    path: PathBuf,
+    /// The directory where the copies of the modules are stored.
+    wasm_dir: PathBuf,
+}
+
+impl DeploymentHistory {
+    /// Create the history for the given network, `sly_dir` is the `.sly` directory of the
+    /// workspace.
+    pub fn new(sly_dir: &Path, network: &str) -> Self {
+        let network = utils::network_file_name(network);
+        let root = sly_dir.join("deployments");
+
+        Self {
+            path: root.join(format!("{}.json", network)),
+            wasm_dir: root.join(network),
+        }
+    }
+
+    /// Return all of the deployments, oldest first.
+    pub fn list(&self) -> Result<Vec<Deployment>> {
+        if !self.path.is_file() {
+            return Ok(Vec::new());
+        }
+
+        let lock = self.lock()?;
+        lock.lock_shared()
+            .with_context(|| format!("Failed to lock {:?}", self.path))?;
+
+        self.read()
+    }
+
+    /// Return the deployments of a canister, oldest first.
+    pub fn list_canister(&self, canister: &str) -> Result<Vec<Deployment>> {
+        Ok(self
+            .list()?
+            .into_iter()
+            .filter(|d| d.canister == canister)
+            .collect())
+    }
+
+    /// Record a new deployment and keep a copy of the wasm.
+    pub fn record(
+        &self,
+        workspace_root: &Path,
+        canister: &str,
+        mode: &str,
+        identity: Principal,
+        wasm: &[u8],
+    ) -> Result<Deployment> {
+        let wasm_sha256 = hex::encode(utils::sha256(wasm));
+
+        fs::create_dir_all(&self.wasm_dir)
+            .with_context(|| format!("Failed to create {:?}", self.wasm_dir))?;
+
+        let wasm_path = self.wasm_path(&wasm_sha256);
+        if !wasm_path.is_file() {
+            utils::write_atomic(&wasm_path, wasm)?;
+        }
+
+        let deployment = Deployment {
+            canister: canister.to_owned(),
+            mode: mode.to_owned(),
+            wasm_sha256,
+            git_commit: get_git_commit(workspace_root),
+            identity,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+        };
+
+        let lock = self.lock()?;
+        lock.lock_exclusive()
+            .with_context(|| format!("Failed to lock {:?}", self.path))?;
+
+        let mut deployments = self.read()?;
+        deployments.push(deployment.clone());
+
+        let json = serde_json::to_string_pretty(&deployments)
+            .context("Failed to serialize the deployments.")?;
+        utils::write_atomic(&self.path, json.as_bytes())?;
+
+        Ok(deployment)
+    }
+
+    /// Read the copy of a previously deployed module.
+    pub fn read_wasm(&self, deployment: &Deployment) -> Result<Vec<u8>> {
+        let path = self.wasm_path(&deployment.wasm_sha256);
+        fs::read(&path).with_context(|| format!("Could not read the stored wasm {:?}", path))
+    }
+
+    fn read(&self) -> Result<Vec<Deployment>> {
+        if !self.path.is_file() {
+            return Ok(Vec::new());
+        }
+
+        let json = fs::read_to_string(&self.path)
+            .with_context(|| format!("Failed to read {:?}", self.path))?;
+        serde_json::from_str(&json).with_context(|| format!("Failed to parse {:?}", self.path))
+    }
+
+    /// Open the lock file of the history, the lock is released once the file is dropped.
+    fn lock(&self) -> Result<fs::File> {
+        let lock_path = self.path.with_extension("json.lock");
+        fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .open(&lock_path)
+            .with_context(|| format!("Failed to open {:?}", lock_path))
+    }
+
+    fn wasm_path(&self, sha256: &str) -> PathBuf {
+        self.wasm_dir.join(format!("{}.wasm", sha256))
+    }
+}
+
+/// Return the current git commit of the given directory.
+fn get_git_commit(dir: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .args(&["rev-parse", "HEAD"])
+        .current_dir(dir)
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
@@ -0,0 +1,379 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use candid::Principal;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+/// The parts of dfx.json that sly knows how to translate, everything else is collected so it
+/// can be reported.
+#[derive(Deserialize)]
+struct DfxJson {
+    canisters: Option<BTreeMap<String, DfxCanister>>,
+    networks: Option<BTreeMap<String, DfxNetwork>>,
+    defaults: Option<Map<String, Value>>,
+    #[serde(flatten)]
+    other: BTreeMap<String, Value>,
+}
+
+#[derive(Deserialize)]
+struct DfxCanister {
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    main: Option<String>,
+    package: Option<String>,
+    source: Option<Vec<String>>,
+    build: Option<Value>,
+    wasm: Option<String>,
+    candid: Option<String>,
+    dependencies: Option<Vec<String>>,
+    remote: Option<DfxRemote>,
+    #[serde(flatten)]
+    other: BTreeMap<String, Value>,
+}
+
+#[derive(Deserialize)]
+struct DfxRemote {
+    candid: Option<String>,
+    #[serde(default)]
+    id: BTreeMap<String, Principal>,
+}
+
+#[derive(Deserialize)]
+struct DfxNetwork {
+    bind: Option<String>,
+    providers: Option<Vec<String>>,
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    #[serde(flatten)]
+    other: BTreeMap<String, Value>,
+}
+
+/// The result of translating a dfx.json to a sly.json.
+pub struct DfxImport {
+    /// The content of the sly.json.
+    pub manifest: Value,
+    /// The parts of dfx.json that could not be translated.
+    pub warnings: Vec<String>,
+}
+
+/// Translate the content of a dfx.json to a sly.json.
+pub fn translate(source: &str) -> Result<DfxImport> {
+    let dfx = serde_json::from_str::<DfxJson>(source).context("Could not parse dfx.json.")?;
+    let mut warnings = Vec::new();
+
+    for key in dfx.other.keys() {
+        // The dfx version and the schema version of dfx.json have no equivalent.
+        if key != "dfx" && key != "version" {
+            warnings.push(format!("'{}' is not supported by sly.json.", key));
+        }
+    }
+
+    for (key, value) in dfx.defaults.unwrap_or_default() {
+        match value.get("packtool").and_then(|x| x.as_str()) {
+            Some(packtool) if key == "build" && !packtool.is_empty() => warnings.push(format!(
+                "dfx uses '{}' to find the Motoko packages, add them to 'packages' in sly.json.",
+                packtool
+            )),
+            _ => warnings.push(format!("'defaults.{}' is not supported by sly.json.", key)),
+        }
+    }
+
+    let mut canisters = Map::new();
+    for (name, canister) in dfx.canisters.unwrap_or_default() {
+        let info = translate_canister(&name, canister, &mut warnings);
+        canisters.insert(name, info);
+    }
+
+    let mut networks = Map::new();
+    for (name, network) in dfx.networks.unwrap_or_default() {
+        if let Some(info) = translate_network(&name, network, &mut warnings) {
+            networks.insert(name, info);
+        }
+    }
+
+    let mut manifest = Map::new();
+    manifest.insert("canisters".into(), Value::Object(canisters));
+    if !networks.is_empty() {
+        manifest.insert("networks".into(), Value::Object(networks));
+    }
+
+    Ok(DfxImport {
+        manifest: Value::Object(manifest),
+        warnings,
+    })
+}
+
+fn translate_canister(name: &str, canister: DfxCanister, warnings: &mut Vec<String>) -> Value {
+    let mut info = Map::new();
+
+    for key in canister.other.keys() {
+        warnings.push(format!(
+            "Canister '{}': '{}' is not supported by sly.json.",
+            name, key
+        ));
+    }
+
+    // Motoko is the default type of dfx.
+    let kind = canister.kind.as_deref().unwrap_or("motoko");
+    let is_custom = match kind {
+        "rust" => {
+            info.insert("type".into(), json!("rust"));
+            if let Some(package) = canister.package {
+                info.insert("package".into(), json!(package));
+            }
+            false
+        }
+        "motoko" => {
+            info.insert("type".into(), json!("motoko"));
+            if let Some(main) = canister.main {
+                info.insert("main".into(), json!(main));
+            }
+            false
+        }
+        "assets" => {
+            info.insert("type".into(), json!("assets"));
+            if let Some(source) = canister.source {
+                info.insert("source".into(), json!(source));
+            }
+            warnings.push(format!(
+                "Canister '{}': dfx builds assets canisters using 'npm run build', add it to \
+                 the build commands if needed.",
+                name
+            ));
+            false
+        }
+        "custom" => true,
+        kind => {
+            warnings.push(format!(
+                "Canister '{}': the type '{}' is not supported, it is translated as a custom \
+                 canister.",
+                name, kind
+            ));
+            true
+        }
+    };
+
+    if is_custom {
+        if let Some(build) = canister.build {
+            info.insert("build".into(), build);
+        }
+
+        if let Some(wasm) = canister.wasm {
+            info.insert("wasm".into(), json!(wasm));
+        }
+    }
+
+    if let Some(candid) = canister.candid {
+        info.insert("candid".into(), json!(candid));
+    }
+
+    if let Some(dependencies) = canister.dependencies {
+        info.insert("dependencies".into(), json!(dependencies));
+    }
+
+    if let Some(remote) = canister.remote {
+        // Only custom canisters keep a wasm that can stand in for the remote canister.
+        if !is_custom {
+            warnings.push(format!(
+                "Canister '{}': sly only deploys a remote canister locally from a custom \
+                 build, the {} settings are dropped.",
+                name, kind
+            ));
+            for key in &["package", "main", "source"] {
+                info.remove(*key);
+            }
+        }
+
+        info.insert("type".into(), json!("remote"));
+        let ids = remote
+            .id
+            .iter()
+            .map(|(network, id)| (network.clone(), id.to_text()))
+            .collect::<BTreeMap<_, _>>();
+        info.insert("remote".into(), json!(ids));
+
+        if let Some(candid) = remote.candid {
+            info.insert("candid".into(), json!(candid));
+        }
+    }
+
+    Value::Object(info)
+}
+
+fn translate_network(name: &str, network: DfxNetwork, warnings: &mut Vec<String>) -> Option<Value> {
+    for key in network.other.keys() {
+        warnings.push(format!(
+            "Network '{}': '{}' is not supported by sly.json.",
+            name, key
+        ));
+    }
+
+    match name {
+        "local" => {
+            warnings.push(
+                "Network 'local': sly manages its own local replica, the settings are dropped."
+                    .to_string(),
+            );
+            return None;
+        }
+        // The main network is predefined in sly.
+        "ic" => return None,
+        _ => {}
+    }
+
+    let providers = network.providers.unwrap_or_default();
+    if providers.len() > 1 {
+        warnings.push(format!(
+            "Network '{}': only the first provider is used.",
+            name
+        ));
+    }
+
+    // Networks served by a local replica are ephemeral unless stated otherwise.
+    let (url, default_kind) = match (providers.into_iter().next(), network.bind) {
+        (Some(url), _) => (url, "persistent"),
+        (None, Some(bind)) => (format!("http://{}", bind), "ephemeral"),
+        (None, None) => {
+            warnings.push(format!(
+                "Network '{}': no provider or bind address, the network is dropped.",
+                name
+            ));
+            return None;
+        }
+    };
+
+    let kind = match network.kind.as_deref() {
+        Some(kind @ "ephemeral") | Some(kind @ "persistent") => kind,
+        Some(kind) => {
+            warnings.push(format!(
+                "Network '{}': unknown type '{}', using '{}'.",
+                name, kind, default_kind
+            ));
+            default_kind
+        }
+        None => default_kind,
+    };
+
+    Some(json!({ "url": url, "type": kind }))
+}
+
+/// Return the canister id files of a dfx project, which are the `canister_ids.json` in the
+/// root for persistent networks and the ones in `.dfx/<network>/` for the others.
+pub fn canister_id_files(root: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    let path = root.join("canister_ids.json");
+    if path.is_file() {
+        files.push(path);
+    }
+
+    let dfx_dir = root.join(".dfx");
+    if dfx_dir.is_dir() {
+        for entry in fs::read_dir(&dfx_dir).context("Failed to read the .dfx directory.")? {
+            let path = entry?.path().join("canister_ids.json");
+            if path.is_file() {
+                files.push(path);
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Read a canister id file of dfx, which maps the name of each canister to its id on every
+/// network.
+pub fn read_canister_ids(path: &Path) -> Result<BTreeMap<String, BTreeMap<String, Principal>>> {
+    let content = fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+    serde_json::from_str(&content).with_context(|| format!("Failed to parse {:?}", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translate_dfx_json() {
+        let dfx = json!({
+            "dfx": "0.8.1",
+            "version": 1,
+            "canisters": {
+                "backend": {
+                    "type": "rust",
+                    "package": "backend",
+                    "candid": "src/backend/backend.did"
+                },
+                "counter": {
+                    "main": "src/counter/main.mo",
+                    "dependencies": ["backend"]
+                },
+                "frontend": {
+                    "type": "assets",
+                    "source": ["dist/frontend/"],
+                    "frontend": { "entrypoint": "src/frontend/index.html" }
+                },
+                "ledger": {
+                    "type": "custom",
+                    "build": "",
+                    "wasm": "ledger.wasm",
+                    "candid": "ledger.private.did",
+                    "remote": {
+                        "candid": "ledger.public.did",
+                        "id": { "ic": "ryjl3-tyaaa-aaaaa-aaaba-cai" }
+                    }
+                }
+            },
+            "defaults": { "build": { "packtool": "vessel sources", "args": "" } },
+            "networks": {
+                "local": { "bind": "127.0.0.1:8000", "type": "ephemeral" },
+                "staging": { "providers": ["https://staging.example.com"] },
+                "devnet": { "bind": "127.0.0.1:9000" }
+            }
+        });
+
+        let result = translate(&dfx.to_string()).unwrap();
+        let expected = json!({
+            "canisters": {
+                "backend": {
+                    "type": "rust",
+                    "package": "backend",
+                    "candid": "src/backend/backend.did"
+                },
+                "counter": {
+                    "type": "motoko",
+                    "main": "src/counter/main.mo",
+                    "dependencies": ["backend"]
+                },
+                "frontend": {
+                    "type": "assets",
+                    "source": ["dist/frontend/"]
+                },
+                "ledger": {
+                    "type": "remote",
+                    "remote": { "ic": "ryjl3-tyaaa-aaaaa-aaaba-cai" },
+                    "build": "",
+                    "wasm": "ledger.wasm",
+                    "candid": "ledger.public.did"
+                }
+            },
+            "networks": {
+                "staging": { "url": "https://staging.example.com", "type": "persistent" },
+                "devnet": { "url": "http://127.0.0.1:9000", "type": "ephemeral" }
+            }
+        });
+
+        assert_eq!(result.manifest, expected);
+        assert!(result.warnings.iter().any(|w| w.contains("vessel sources")));
+        assert!(result
+            .warnings
+            .iter()
+            .any(|w| w.contains("Canister 'frontend': 'frontend'")));
+        assert!(result
+            .warnings
+            .iter()
+            .any(|w| w.contains("Network 'local'")));
+    }
+}
@@ -0,0 +1,80 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+
+/// Load the variables from the given `.env` file, returns an empty map if the file does
+/// not exist.
+pub fn load(path: &Path) -> Result<BTreeMap<String, String>> {
+    if !path.is_file() {
+        return Ok(BTreeMap::new());
+    }
+
+    let content = fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+    parse(&content).with_context(|| format!("Failed to parse {:?}", path))
+}
+
+/// Parse the content of a `.env` file, every line is either empty, a comment starting with
+/// `#` or a `KEY=VALUE` pair, optionally prefixed with `export`. Values can be quoted.
+pub fn parse(content: &str) -> Result<BTreeMap<String, String>> {
+    let mut result = BTreeMap::new();
+
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value) = match line.find('=') {
+            Some(i) => (line[..i].trim(), line[i + 1..].trim()),
+            None => bail!("Expected KEY=VALUE at line {}.", index + 1),
+        };
+
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            bail!("Invalid variable name '{}' at line {}.", key, index + 1);
+        }
+
+        result.insert(key.to_owned(), unquote(value).to_owned());
+    }
+
+    Ok(result)
+}
+
+fn unquote(value: &str) -> &str {
+    for quote in &['"', '\''] {
+        if value.len() >= 2 && value.starts_with(*quote) && value.ends_with(*quote) {
+            return &value[1..value.len() - 1];
+        }
+    }
+
+    // Drop trailing comments of unquoted values.
+    match value.find(" #") {
+        Some(i) => value[..i].trim_end(),
+        None => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_dotenv() {
+        let vars = parse(
+            "# comment\n\
+             A=1\n\
+             export B = \"two words\"\n\
+             C='x # y'\n\
+             D=value # comment\n",
+        )
+        .unwrap();
+
+        assert_eq!(vars["A"], "1");
+        assert_eq!(vars["B"], "two words");
+        assert_eq!(vars["C"], "x # y");
+        assert_eq!(vars["D"], "value");
+        assert!(parse("NOT VALID").is_err());
+    }
+}
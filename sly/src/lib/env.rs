@@ -1,31 +1,45 @@
 use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use std::sync::Mutex;
 
-use anyhow::{bail, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use dirs::config_dir;
+use ic_agent::ic_types::Principal;
 use ic_agent::{agent, Agent, Identity};
 
+use crate::lib::canister_ids::CanisterIdStore;
+use crate::lib::deployments::DeploymentHistory;
 use crate::lib::identity_store::IdentityStore;
+use crate::lib::network::{Network, RootKeyPolicy};
 use crate::lib::toolchain;
 use crate::lib::workspace::Workspace;
 
-pub static MAIN_IC_NETWORK: &str = "https://ic0.app";
-
 pub struct Env {
     network: String,
     ic_server: Mutex<RefCell<Option<String>>>,
     workspace: Mutex<RefCell<Option<Workspace>>>,
     config_path: Option<PathBuf>,
+    /// The profile of sly.json whose overrides are applied.
+    profile: Option<String>,
     identity: String,
     identity_store: IdentityStore,
+    /// Whether the network and the identity were chosen by the user, in which case they
+    /// take precedence over the defaults of the scripts.
+    explicit_network: bool,
+    explicit_identity: bool,
+    /// The directory used to store the state of the workspace when it is deployed to an
+    /// isolated replica, instead of the `.sly` directory.
+    isolated_state_dir: Option<PathBuf>,
 }
 
 impl Env {
-    /// Create a new env.
+    /// Create a new env, the `local` network and the current identity of the identity store
+    /// are used when they are not provided.
     pub fn new(
-        network: String,
+        network: Option<String>,
         identity: Option<&str>,
+        profile: Option<String>,
         config_path: Option<PathBuf>,
     ) -> Result<Self> {
         let directory = config_dir()
@@ -35,6 +49,10 @@ impl Env {
 
         let identity_store = IdentityStore::load(&directory)?;
 
+        let explicit_network = network.is_some();
+        let explicit_identity = identity.is_some();
+        let network = network.unwrap_or_else(|| "local".to_string());
+
         let identity = if let Some(name) = identity {
             if identity_store.get_identity(name).is_none() {
                 bail!("Identity '{}' does not exists.", name);
@@ -50,11 +68,36 @@ impl Env {
             ic_server: Mutex::new(RefCell::new(None)),
             workspace: Mutex::new(RefCell::new(None)),
             config_path,
+            profile,
             identity,
             identity_store,
+            explicit_network,
+            explicit_identity,
+            isolated_state_dir: None,
         })
     }
 
+    /// Create the env that a script runs in, using the network and identity of the script
+    /// unless they were chosen by the user.
+    pub fn for_script(&self, network: Option<&str>, identity: Option<&str>) -> Result<Self> {
+        let network = match network {
+            Some(network) if !self.explicit_network => network.to_owned(),
+            _ => self.network.clone(),
+        };
+
+        let identity = match identity {
+            Some(identity) if !self.explicit_identity => identity,
+            _ => self.identity.as_str(),
+        };
+
+        Env::new(
+            Some(network),
+            Some(identity),
+            self.profile.clone(),
+            self.config_path.clone(),
+        )
+    }
+
     /// Return a mutable reference to the identity store
     pub fn get_identity_store_mut(&mut self) -> &mut IdentityStore {
         &mut self.identity_store
@@ -66,7 +109,12 @@ impl Env {
         let net = lock.borrow_mut();
 
         if net.is_none() {
-            let value = parse_network(self.network.as_str())?;
+            let value = match self.network_config()?.url {
+                Some(url) => url,
+                None => {
+                    get_local_network().context("Failed to find the address for local replica.")?
+                }
+            };
             return Ok(value
                 .strip_suffix('/')
                 .unwrap_or_else(|| value.as_str())
@@ -76,9 +124,25 @@ impl Env {
         Ok(net.as_ref().unwrap().to_owned())
     }
 
-    /// Returns `true` if the current replica that should be used is the IC main network.
-    pub fn is_ic_net(&self) -> Result<bool> {
-        Ok(self.ic_url()? == MAIN_IC_NETWORK)
+    /// Return the settings of the current network, the networks declared in sly.json take
+    /// precedence over the predefined `ic` and `local` networks. A raw URL can also be used
+    /// as the network.
+    pub fn network_config(&self) -> Result<Network> {
+        if let Some(workspace) = self.try_workspace()? {
+            if let Some(network) = workspace.networks.get(&self.network) {
+                return Ok(network.clone());
+            }
+        }
+
+        if let Some(network) = Network::predefined(&self.network) {
+            return Ok(network);
+        }
+
+        if self.network.contains("://") {
+            return Ok(Network::from_url(&self.network));
+        }
+
+        bail!("Network '{}' is not defined in sly.json.", self.network)
     }
 
     /// Return the name of the identity that should be used.
@@ -86,6 +150,17 @@ impl Env {
         self.identity.as_str()
     }
 
+    /// Return the principal id of the wallet that should be used to pay for cycles.
+    pub fn wallet(&self) -> Result<Principal> {
+        let network = self.network()?;
+        self.network_config()?.wallet.ok_or_else(|| {
+            anyhow!(
+                "Network '{}' does not have a wallet, set one in the networks section of sly.json.",
+                network
+            )
+        })
+    }
+
     /// Return the current identity that should be used.
     pub fn current_identity(&self) -> Box<dyn Identity> {
         let name = self.identity.as_str();
@@ -106,7 +181,7 @@ impl Env {
             .build()
             .context("Failed to build the Agent")?;
 
-        if !self.is_ic_net().unwrap() {
+        if self.network_config()?.root_key == RootKeyPolicy::Fetch {
             log::trace!("Fetching the root key for replica: {}", url);
             agent
                 .fetch_root_key()
@@ -127,10 +202,11 @@ impl Env {
             return Ok(workspace.as_ref().unwrap().clone());
         }
 
+        let profile = self.profile.as_deref();
         let w = if let Some(path) = &self.config_path {
-            Workspace::from_config_path(path.clone())
+            Workspace::from_config_path(path.clone(), profile)
         } else {
-            Workspace::from_current_directory()
+            Workspace::from_current_directory(profile)
         }
         .context("Loading sly.json failed.")?;
 
@@ -138,25 +214,229 @@ impl Env {
         Ok(w)
     }
 
-    pub fn network(&self) -> String {
-        match self.network.as_str() {
-            "ic" => "ic".to_string(),
-            "local" => "local".to_string(),
-            _ => self.ic_url().unwrap(),
+    /// Return the current workspace, or `None` if there is no sly.json to load. Unlike a
+    /// missing manifest, a manifest that fails to load is an error.
+    pub fn try_workspace(&self) -> anyhow::Result<Option<Workspace>> {
+        if self.config_path.is_none() && Workspace::find_manifest()?.is_none() {
+            return Ok(None);
+        }
+
+        self.workspace().map(Some)
+    }
+
+    /// Use the local replica listening on the given url instead of the shared one, the canister
+    /// ids and the rest of the deploy state are kept in `state_dir` so they do not mix with
+    /// the ones of the shared local replica.
+    pub fn use_isolated_replica(&mut self, url: String, state_dir: PathBuf) {
+        self.network = "local".to_string();
+        *self.ic_server.lock().unwrap().borrow_mut() = Some(url);
+        self.isolated_state_dir = Some(state_dir);
+    }
+
+    /// Return the directory where sly stores the deploy state of the workspace, this is the
+    /// `.sly` directory unless an isolated replica is used.
+    pub fn state_dir(&self) -> Result<PathBuf> {
+        match &self.isolated_state_dir {
+            Some(dir) => Ok(dir.clone()),
+            None => Ok(self.workspace()?.sly_dir()),
         }
     }
-}
 
-fn parse_network(network: &str) -> Result<String> {
-    match network {
-        "ic" => Ok(MAIN_IC_NETWORK.to_owned()),
-        "local" => get_local_network().context("Failed to find the address for local replica."),
-        network => Ok(network.to_owned()),
+    /// Return the store for the canister ids of the workspace on the current network.
+    pub fn canister_id_store(&self) -> Result<CanisterIdStore> {
+        let root = match &self.isolated_state_dir {
+            Some(dir) => dir.clone(),
+            None => self.workspace()?.root,
+        };
+
+        let is_ephemeral = self.network_config()?.is_ephemeral();
+        Ok(CanisterIdStore::new(&root, &self.network()?, is_ephemeral))
+    }
+
+    /// Return the deployment history of the workspace on the current network.
+    pub fn deployment_history(&self) -> Result<DeploymentHistory> {
+        Ok(DeploymentHistory::new(&self.state_dir()?, &self.network()?))
+    }
+
+    /// Return the id of every known canister on the current network, this includes the ids
+    /// of the remote canisters declared in sly.json.
+    pub fn canister_ids(&self) -> Result<BTreeMap<String, Principal>> {
+        let workspace = self.workspace()?;
+        let network = self.network()?;
+        let mut ids = self.canister_id_store()?.get_all()?;
+
+        for (name, canister) in &workspace.canisters {
+            if let Some(canister_id) = canister.remote_id(&network) {
+                ids.insert(name.clone(), canister_id);
+            }
+        }
+
+        Ok(ids)
+    }
+
+    /// Return the environment variables that are passed to the build, test and hook commands
+    /// of a canister.
+    pub fn command_envs(&self, canister: &str, mode: &str) -> Result<Vec<(String, String)>> {
+        let mut envs = self.workspace_envs(mode)?;
+        envs.push(("SLY_CANISTER_NAME".to_string(), canister.to_string()));
+        Ok(envs)
+    }
+
+    /// Return the environment variables that are passed to every command that sly runs in
+    /// the workspace.
+    pub fn workspace_envs(&self, mode: &str) -> Result<Vec<(String, String)>> {
+        let workspace = self.workspace()?;
+        let mut envs = workspace.dotenv.clone().into_iter().collect::<Vec<_>>();
+
+        envs.push((CHILD_PROCESS_VAR.to_string(), "1".to_string()));
+        envs.push(("SLY_NETWORK".to_string(), self.network()?));
+        envs.push(("SLY_IDENTITY".to_string(), self.identity.clone()));
+        if let Some(profile) = &self.profile {
+            envs.push(("SLY_PROFILE".to_string(), profile.clone()));
+        }
+        envs.push(("SLY_MODE".to_string(), mode.to_string()));
+        let root = workspace
+            .root
+            .canonicalize()
+            .unwrap_or_else(|_| workspace.root.clone());
+        envs.push((
+            "SLY_WORKSPACE_ROOT".to_string(),
+            root.to_string_lossy().to_string(),
+        ));
+
+        for (name, canister_id) in self.canister_ids()? {
+            envs.push((canister_id_env_var(&name), canister_id.to_text()));
+        }
+
+        Ok(envs)
+    }
+
+    /// Return an error if the given controllers would leave the current identity, and on
+    /// persistent networks the wallet, unable to manage the canister.
+    pub fn ensure_caller_is_controller(
+        &self,
+        canister: &str,
+        controllers: &[Principal],
+    ) -> Result<()> {
+        let identity = self.current_identity().sender().map_err(|e| anyhow!(e))?;
+        if controllers.contains(&identity) {
+            return Ok(());
+        }
+
+        let network = self.network_config()?;
+        if let (false, Some(wallet)) = (network.is_ephemeral(), network.wallet) {
+            if controllers.contains(&wallet) {
+                return Ok(());
+            }
+        }
+
+        bail!(
+            "Neither the identity '{}' ({}) nor the wallet of the network is a controller of '{}' in sly.json, sly would lose control of the canister.",
+            self.identity,
+            identity,
+            canister
+        )
+    }
+
+    /// Resolve a principal that is referenced either by the name of an identity or by its
+    /// text representation.
+    pub fn resolve_principal(&self, principal: &str) -> Result<Principal> {
+        if let Some(identity) = self.identity_store.get_identity(principal) {
+            return identity.sender().map_err(|e| anyhow!(e));
+        }
+
+        Principal::from_text(principal).with_context(|| {
+            format!(
+                "'{}' is neither the name of an identity nor a valid principal id.",
+                principal
+            )
+        })
+    }
+
+    /// Return the controllers of a canister on the current network as declared in sly.json,
+    /// or `None` if the canister does not specify any controllers.
+    pub fn canister_controllers(&self, canister: &str) -> Result<Option<Vec<Principal>>> {
+        let workspace = self.workspace()?;
+        let canister_info = workspace
+            .get_canister(canister)
+            .ok_or_else(|| anyhow!("Canister '{}' not found.", canister))?;
+
+        match canister_info.controllers(&self.network()?) {
+            Some(controllers) => Ok(Some(
+                controllers
+                    .iter()
+                    .map(|c| self.resolve_principal(c))
+                    .collect::<Result<_>>()?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Resolve a canister that is referenced either by its name in the workspace or by its
+    /// principal id.
+    pub fn resolve_canister_id(&self, canister: &str) -> Result<Principal> {
+        if let Some(workspace) = self.try_workspace()? {
+            if workspace.get_canister(canister).is_some() {
+                let network = self.network()?;
+                return self.canister_ids()?.remove(canister).ok_or_else(|| {
+                    anyhow!(
+                        "Canister '{}' is not created on network '{}'.",
+                        canister,
+                        network
+                    )
+                });
+            }
+        }
+
+        Principal::from_text(canister).with_context(|| {
+            format!(
+                "'{}' is neither a canister in sly.json nor a valid principal id.",
+                canister
+            )
+        })
     }
+
+    /// Return the name of the current network, which is used to key the canister ids and the
+    /// rest of the deploy state. This is the URL for networks that are not named.
+    pub fn network(&self) -> Result<String> {
+        let is_named = Network::predefined(&self.network).is_some()
+            || match self.try_workspace()? {
+                Some(workspace) => workspace.networks.contains_key(&self.network),
+                None => false,
+            };
+
+        if is_named {
+            Ok(self.network.clone())
+        } else {
+            self.ic_url()
+        }
+    }
+}
+
+/// The variable that marks the commands spawned by sly, only these inherit the settings of
+/// the parent process.
+pub static CHILD_PROCESS_VAR: &str = "SLY_CHILD_PROCESS";
+
+/// Return the value of a variable set by the parent sly process, the variable is ignored if
+/// the current process was not spawned by sly, e.g when it is left over in the shell.
+pub fn inherited_var(name: &str) -> Option<String> {
+    std::env::var_os(CHILD_PROCESS_VAR)?;
+    let value = std::env::var(name).ok()?;
+    log::debug!("Using {}={} from the parent process.", name, value);
+    Some(value)
+}
+
+/// Return the name of the environment variable that holds the id of the given canister.
+pub fn canister_id_env_var(name: &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    format!("SLY_CANISTER_ID_{}", name.to_ascii_uppercase())
 }
 
 fn get_local_network() -> Result<String> {
-    let port_file = toolchain::get_replica_port_file()?;
+    let port_file = toolchain::get_replica_port_file(&toolchain::get_replica_state_root()?);
 
     if !port_file.is_file() {
         bail!("Local replica is not running.")
@@ -0,0 +1,488 @@
+use std::fmt;
+use std::ops::Range;
+
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+use codespan_reporting::files::SimpleFile;
+use codespan_reporting::term;
+use codespan_reporting::term::termcolor::{NoColor, WriteColor};
+
+/// A segment of the path to a value in a json document.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+impl From<&str> for PathSegment {
+    fn from(key: &str) -> Self {
+        PathSegment::Key(key.to_owned())
+    }
+}
+
+/// A manifest file that contributes to the resolved manifest.
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    pub name: String,
+    pub source: String,
+}
+
+impl SourceFile {
+    pub fn new(name: &str, source: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            source: source.to_owned(),
+        }
+    }
+}
+
+/// An error in a manifest file, which is displayed along with the part of the source that
+/// caused it.
+#[derive(Debug)]
+pub struct ManifestError {
+    file: SimpleFile<String, String>,
+    diagnostic: Diagnostic<()>,
+}
+
+impl ManifestError {
+    /// Create an error for a file that is not valid json.
+    pub fn syntax(name: &str, source: &str, error: &serde_json::Error) -> Self {
+        let offset = line_column_offset(source, error.line(), error.column());
+        let end = (offset + 1).min(source.len());
+        let message = strip_position(&error.to_string());
+
+        Self::new(
+            name,
+            source,
+            Diagnostic::error()
+                .with_message(format!("{} is not valid json.", name))
+                .with_labels(vec![Label::primary((), offset..end).with_message(message)]),
+        )
+    }
+
+    /// Create an error for a value that does not match the schema of the manifest. The value
+    /// can come from more than one place, so the first of the candidate paths that exists in
+    /// one of the files is used to locate it, `path` is the one that was deserialized. The
+    /// files are ordered from the most specific one, i.e the extending manifest comes first.
+    pub fn invalid(
+        files: &[SourceFile],
+        path: &[PathSegment],
+        candidates: &[Vec<PathSegment>],
+        error: &serde_json::Error,
+    ) -> Self {
+        let message = error.to_string();
+
+        // Unknown fields are reported on the object that contains them.
+        if let Some(field) = message
+            .strip_prefix("unknown field `")
+            .and_then(|rest| rest.split('`').next())
+        {
+            let (file, range) = locate(files, candidates, |source, path| {
+                find_key_span(source, path, field)
+            });
+
+            return Self::new(
+                &file.name,
+                &file.source,
+                with_label(
+                    Diagnostic::error().with_message(format!("Unknown field '{}'.", field)),
+                    range,
+                    message,
+                    path,
+                ),
+            );
+        }
+
+        let (file, range) = locate(files, candidates, find_value_span);
+        let field = match path.last() {
+            Some(PathSegment::Key(key)) => key.clone(),
+            Some(PathSegment::Index(index)) => format!("[{}]", index),
+            None => "sly.json".to_owned(),
+        };
+
+        // The errors of untagged enums do not tell what was expected.
+        let (label, notes) = if message.starts_with("data did not match any variant") {
+            (
+                "this value does not have one of the accepted types".to_owned(),
+                vec!["Run `sly config schema` to see the accepted values.".to_owned()],
+            )
+        } else {
+            (message, vec![])
+        };
+
+        let diagnostic = Diagnostic::error()
+            .with_message(format!("Invalid value for '{}'.", field))
+            .with_notes(notes);
+
+        Self::new(
+            &file.name,
+            &file.source,
+            with_label(diagnostic, range, label, path),
+        )
+    }
+
+    /// Create an error with the given message for the value at the given path, in the first
+    /// of the files that contains it.
+    pub fn at(files: &[SourceFile], path: &[PathSegment], message: String) -> Self {
+        let (file, range) = locate(files, &[path.to_vec()], find_value_span);
+
+        Self::new(
+            &file.name,
+            &file.source,
+            with_label(Diagnostic::error(), range, String::new(), path).with_message(message),
+        )
+    }
+
+    fn new(name: &str, source: &str, diagnostic: Diagnostic<()>) -> Self {
+        Self {
+            file: SimpleFile::new(name.to_owned(), source.to_owned()),
+            diagnostic,
+        }
+    }
+
+    /// Write the diagnostic to the given writer.
+    pub fn emit(
+        &self,
+        writer: &mut dyn WriteColor,
+    ) -> Result<(), codespan_reporting::files::Error> {
+        let config = term::Config::default();
+        term::emit(writer, &config, &self.file, &self.diagnostic)
+    }
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut writer = NoColor::new(Vec::new());
+        self.emit(&mut writer).map_err(|_| fmt::Error)?;
+        write!(
+            f,
+            "{}",
+            String::from_utf8_lossy(&writer.into_inner()).trim_end()
+        )
+    }
+}
+
+impl std::error::Error for ManifestError {}
+
+/// Return the file and the span of the first candidate path that `find` locates, the candidate
+/// paths take precedence over the order of the files. The first file is returned without a
+/// span if none of the paths is found.
+fn locate<'a, F>(
+    files: &'a [SourceFile],
+    candidates: &[Vec<PathSegment>],
+    find: F,
+) -> (&'a SourceFile, Option<Range<usize>>)
+where
+    F: Fn(&str, &[PathSegment]) -> Option<Range<usize>>,
+{
+    for path in candidates {
+        for file in files {
+            if let Some(range) = find(&file.source, path) {
+                return (file, Some(range));
+            }
+        }
+    }
+
+    (&files[0], None)
+}
+
+/// Point the diagnostic at the given range, or mention the path when the value can not be
+/// located in the source, e.g when it comes from an extended manifest.
+fn with_label(
+    diagnostic: Diagnostic<()>,
+    range: Option<Range<usize>>,
+    message: String,
+    path: &[PathSegment],
+) -> Diagnostic<()> {
+    match range {
+        Some(range) => {
+            diagnostic.with_labels(vec![Label::primary((), range).with_message(message)])
+        }
+        None => {
+            let path = path
+                .iter()
+                .map(|segment| match segment {
+                    PathSegment::Key(key) => key.clone(),
+                    PathSegment::Index(index) => index.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(".");
+
+            let note = if message.is_empty() {
+                format!("at '{}'", path)
+            } else {
+                format!("at '{}': {}", path, message)
+            };
+
+            let mut diagnostic = diagnostic;
+            diagnostic.notes.insert(0, note);
+            diagnostic
+        }
+    }
+}
+
+/// Convert the path of a deserialization error to a list of segments.
+pub fn path_segments(path: &serde_path_to_error::Path) -> Vec<PathSegment> {
+    use serde_path_to_error::Segment;
+
+    let mut result = Vec::new();
+
+    // The segments after an unknown one can not be located.
+    for segment in path.iter() {
+        match segment {
+            Segment::Map { key } => result.push(PathSegment::Key(key.clone())),
+            Segment::Seq { index } => result.push(PathSegment::Index(*index)),
+            Segment::Enum { .. } | Segment::Unknown => break,
+        }
+    }
+
+    result
+}
+
+/// Remove the ` at line x column y` suffix that serde_json adds to its messages.
+fn strip_position(message: &str) -> String {
+    match message.rfind(" at line ") {
+        Some(index) => message[..index].to_owned(),
+        None => message.to_owned(),
+    }
+}
+
+/// Convert a 1-based line and column to an offset in the source.
+fn line_column_offset(source: &str, line: usize, column: usize) -> usize {
+    let line_start = source
+        .split_inclusive('\n')
+        .take(line.saturating_sub(1))
+        .map(|l| l.len())
+        .sum::<usize>();
+
+    (line_start + column.saturating_sub(1)).min(source.len())
+}
+
+/// Return the span of the value at the given path in a json document.
+pub fn find_value_span(source: &str, path: &[PathSegment]) -> Option<Range<usize>> {
+    let mut scanner = Scanner::new(source);
+    scanner.seek(path)?;
+    let start = scanner.pos;
+    scanner.skip_value()?;
+    Some(start..scanner.pos)
+}
+
+/// Return the span of the given key in the object at the given path.
+pub fn find_key_span(source: &str, path: &[PathSegment], key: &str) -> Option<Range<usize>> {
+    let mut scanner = Scanner::new(source);
+    scanner.seek(path)?;
+    scanner.find_key(key)
+}
+
+/// A minimal json scanner that is used to find the position of values in a document that is
+/// already known to be valid.
+struct Scanner<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(source: &'a str) -> Self {
+        Self {
+            bytes: source.as_bytes(),
+            pos: 0,
+        }
+    }
+
+    /// Move to the start of the value at the given path.
+    fn seek(&mut self, path: &[PathSegment]) -> Option<()> {
+        self.skip_whitespace();
+
+        for segment in path {
+            match segment {
+                PathSegment::Key(key) => {
+                    self.find_key(key)?;
+                    self.skip_whitespace();
+                    self.expect(b':')?;
+                }
+                PathSegment::Index(index) => {
+                    self.expect(b'[')?;
+                    for _ in 0..*index {
+                        self.skip_whitespace();
+                        self.skip_value()?;
+                        self.skip_whitespace();
+                        self.expect(b',')?;
+                    }
+                }
+            }
+
+            self.skip_whitespace();
+        }
+
+        Some(())
+    }
+
+    /// Move past the given key of the object that starts at the current position and return
+    /// the span of the key.
+    fn find_key(&mut self, key: &str) -> Option<Range<usize>> {
+        self.expect(b'{')?;
+
+        loop {
+            self.skip_whitespace();
+            let start = self.pos;
+            let current = self.read_string()?;
+            let range = start..self.pos;
+
+            if current == key {
+                return Some(range);
+            }
+
+            self.skip_whitespace();
+            self.expect(b':')?;
+            self.skip_whitespace();
+            self.skip_value()?;
+            self.skip_whitespace();
+            self.expect(b',')?;
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.bytes.len() && self.bytes[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Option<()> {
+        if self.bytes.get(self.pos) == Some(&byte) {
+            self.pos += 1;
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    /// Read a string, only the escaped quotes and backslashes are decoded since keys are not
+    /// expected to contain other escapes.
+    fn read_string(&mut self) -> Option<String> {
+        self.expect(b'"')?;
+        let mut result = Vec::new();
+
+        while let Some(&byte) = self.bytes.get(self.pos) {
+            self.pos += 1;
+            match byte {
+                b'"' => return String::from_utf8(result).ok(),
+                b'\\' => {
+                    result.push(*self.bytes.get(self.pos)?);
+                    self.pos += 1;
+                }
+                _ => result.push(byte),
+            }
+        }
+
+        None
+    }
+
+    fn skip_value(&mut self) -> Option<()> {
+        match self.bytes.get(self.pos)? {
+            b'"' => self.read_string().map(|_| ()),
+            b'{' | b'[' => {
+                let mut depth = 0;
+                while let Some(&byte) = self.bytes.get(self.pos) {
+                    match byte {
+                        b'"' => {
+                            self.read_string()?;
+                            continue;
+                        }
+                        b'{' | b'[' => depth += 1,
+                        b'}' | b']' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                self.pos += 1;
+                                return Some(());
+                            }
+                        }
+                        _ => {}
+                    }
+                    self.pos += 1;
+                }
+                None
+            }
+            _ => {
+                while let Some(byte) = self.bytes.get(self.pos) {
+                    if matches!(byte, b',' | b'}' | b']') || byte.is_ascii_whitespace() {
+                        break;
+                    }
+                    self.pos += 1;
+                }
+                Some(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"{
+  "canisters": {
+    "a": { "build": ["x", "y \" z"], "wasm": "a.wasm" },
+    "b": { "biuld": "b" }
+  }
+}"#;
+
+    #[test]
+    fn value_span() {
+        let path = vec!["canisters".into(), "a".into(), "build".into()];
+        let range = find_value_span(SOURCE, &path).unwrap();
+        assert_eq!(&SOURCE[range], r#"["x", "y \" z"]"#);
+
+        let path = vec![
+            "canisters".into(),
+            "a".into(),
+            "build".into(),
+            PathSegment::Index(1),
+        ];
+        let range = find_value_span(SOURCE, &path).unwrap();
+        assert_eq!(&SOURCE[range], r#""y \" z""#);
+
+        let path = vec!["canisters".into(), "a".into(), "wasm".into()];
+        let range = find_value_span(SOURCE, &path).unwrap();
+        assert_eq!(&SOURCE[range], r#""a.wasm""#);
+
+        let path = vec!["canisters".into(), "c".into()];
+        assert!(find_value_span(SOURCE, &path).is_none());
+    }
+
+    #[test]
+    fn key_span() {
+        let path = vec!["canisters".into(), "b".into()];
+        let range = find_key_span(SOURCE, &path, "biuld").unwrap();
+        assert_eq!(&SOURCE[range], r#""biuld""#);
+    }
+
+    #[test]
+    fn locate_in_extended_file() {
+        let files = vec![
+            SourceFile::new(
+                "sly.json",
+                r#"{ "canisters": { "a": { "wasm": "a.wasm" } } }"#,
+            ),
+            SourceFile::new("base.json", SOURCE),
+        ];
+
+        let path = vec!["canisters".into(), "b".into()];
+        let error = ManifestError::at(&files, &path, "Invalid canister.".to_owned());
+        assert_eq!(error.file.name(), "base.json");
+
+        let path = vec!["canisters".into(), "a".into(), "wasm".into()];
+        let (file, range) = locate(&files, &[path], find_value_span);
+        assert_eq!(file.name, "sly.json");
+        assert_eq!(&file.source[range.unwrap()], r#""a.wasm""#);
+
+        let path = vec!["canisters".into(), "c".into()];
+        let (file, range) = locate(&files, &[path], find_value_span);
+        assert_eq!(file.name, "sly.json");
+        assert!(range.is_none());
+    }
+
+    #[test]
+    fn syntax_error_offset() {
+        assert_eq!(line_column_offset("ab\ncd", 2, 2), 4);
+        assert_eq!(line_column_offset("ab", 5, 1), 2);
+    }
+}
@@ -1,9 +1,22 @@
+pub mod assets;
+pub mod build_cache;
 pub mod candid;
+pub mod candid_compat;
+pub mod canister_ids;
 pub mod command;
+pub mod deployments;
 pub mod dfx;
+pub mod dfx_import;
+pub mod dotenv;
 pub mod env;
 pub mod identity_store;
+pub mod manifest_error;
+pub mod motoko;
+pub mod network;
 pub mod private_key;
+pub mod rust;
+pub mod shell;
 pub mod toolchain;
 pub mod utils;
+pub mod wasm;
 pub mod workspace;
@@ -0,0 +1,76 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::lib::{dfx, shell, toolchain};
+
+/// Return the path to the moc compiler, falls back to the one in the `PATH` if the toolchain
+/// can not be found.
+pub fn moc_path() -> String {
+    match toolchain::get_binary_command_path("moc") {
+        Ok(path) => path.to_string_lossy().to_string(),
+        Err(e) => {
+            log::debug!("Could not find the toolchain's moc: {:?}", e);
+            "moc".into()
+        }
+    }
+}
+
+/// Return the path to the Motoko base library that ships with the toolchain.
+pub fn base_library_path() -> Option<PathBuf> {
+    let path = dfx::get_dfx_bin_root().ok()?.join("base");
+    if path.is_dir() {
+        Some(path)
+    } else {
+        None
+    }
+}
+
+/// Return the directory where the outputs of a Motoko canister are written to, relative to
+/// the workspace root.
+pub fn output_dir(name: &str, mode: &str) -> PathBuf {
+    Path::new(".sly").join("build").join(name).join(mode)
+}
+
+/// Return the commands that compile the given main file to a wasm and a candid file in the
+/// given output directory.
+pub fn build_commands(
+    moc: &str,
+    main: &str,
+    output_dir: &Path,
+    name: &str,
+    packages: &BTreeMap<String, String>,
+    mode: &str,
+) -> Vec<String> {
+    let mut flags = String::new();
+    for (package, path) in packages {
+        flags.push_str(&format!(
+            " --package {} {}",
+            shell::quote(package),
+            shell::quote(path)
+        ));
+    }
+
+    if mode != "debug" {
+        flags.push_str(" --release");
+    }
+
+    let output = output_dir.join(name).to_string_lossy().to_string();
+
+    vec![
+        format!("mkdir -p {}", shell::quote(&output_dir.to_string_lossy())),
+        format!(
+            "{} {}{} --idl -o {}",
+            shell::quote(moc),
+            shell::quote(main),
+            flags,
+            shell::quote(&format!("{}.did", output))
+        ),
+        format!(
+            "{} {}{} -c -o {}",
+            shell::quote(moc),
+            shell::quote(main),
+            flags,
+            shell::quote(&format!("{}.wasm", output))
+        ),
+    ]
+}
@@ -0,0 +1,83 @@
+use candid::Principal;
+use serde::{Deserialize, Serialize};
+
+/// The URL of the main network of the Internet Computer.
+pub static MAIN_IC_NETWORK: &str = "https://ic0.app";
+
+/// The canister id of the XTC token, which we use as the cycles wallet on the main network.
+pub static XTC_CANISTER_ID: &str = "aanaa-xaaaa-aaaah-aaeiq-cai";
+
+/// A network that canisters can be deployed to.
+#[derive(Debug, Clone)]
+pub struct Network {
+    /// The URL of the replica, `None` means the local replica started by `sly replica start`
+    /// whose address is read from the port file.
+    pub url: Option<String>,
+    pub kind: NetworkKind,
+    pub root_key: RootKeyPolicy,
+    /// The wallet that pays for the cycles of the canisters created on this network.
+    pub wallet: Option<Principal>,
+}
+
+/// Whether the state of a network outlives the development session.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkKind {
+    /// A network that is reset often, e.g the local replica. The canister ids are stored in
+    /// their own file so they can be ignored by git, and canisters are created with
+    /// provisional cycles.
+    Ephemeral,
+    /// A network whose canisters are expected to live for a long time.
+    Persistent,
+}
+
+/// How the agent obtains the root key of a network.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RootKeyPolicy {
+    /// Fetch the root key from the replica, this is only safe for development networks.
+    Fetch,
+    /// Trust the root key of the main network that is built into the agent.
+    Builtin,
+}
+
+impl Network {
+    /// Return the network that is predefined for the given name.
+    pub fn predefined(name: &str) -> Option<Self> {
+        match name {
+            "ic" => Some(Self {
+                url: Some(MAIN_IC_NETWORK.to_owned()),
+                kind: NetworkKind::Persistent,
+                root_key: RootKeyPolicy::Builtin,
+                wallet: Some(Principal::from_text(XTC_CANISTER_ID).unwrap()),
+            }),
+            "local" => Some(Self {
+                url: None,
+                kind: NetworkKind::Ephemeral,
+                root_key: RootKeyPolicy::Fetch,
+                wallet: None,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Return the network for a raw URL that is passed as the network name.
+    pub fn from_url(url: &str) -> Self {
+        // The main network keeps its settings when it is referenced by its URL.
+        if url.trim_end_matches('/') == MAIN_IC_NETWORK {
+            return Self::predefined("ic").unwrap();
+        }
+
+        Self {
+            url: Some(url.to_owned()),
+            kind: NetworkKind::Persistent,
+            root_key: RootKeyPolicy::Fetch,
+            wallet: None,
+        }
+    }
+
+    /// Returns `true` if the state of the network does not outlive the development session.
+    pub fn is_ephemeral(&self) -> bool {
+        self.kind == NetworkKind::Ephemeral
+    }
+}
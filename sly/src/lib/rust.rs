@@ -0,0 +1,187 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+
+/// The target that Rust canisters are compiled to.
+pub const WASM_TARGET: &str = "wasm32-unknown-unknown";
+
+/// Return the cargo profile that is used for the given build mode.
+pub fn profile_for_mode(mode: &str) -> &'static str {
+    match mode {
+        "debug" => "debug",
+        _ => "release",
+    }
+}
+
+/// Return the cargo command that builds the given package for the given profile.
+pub fn build_command(package: &str, profile: &str) -> String {
+    let mut command = format!("cargo build --target {} --package {}", WASM_TARGET, package);
+    if profile == "release" {
+        command.push_str(" --release");
+    }
+    command
+}
+
+/// The parts of the cargo metadata of a workspace that are used to locate the wasm files.
+pub struct CargoMetadata {
+    /// The target directory of the cargo workspace.
+    pub target_dir: PathBuf,
+    /// The name of the cdylib target of each package that has one, which is the `[lib] name`
+    /// when it is set.
+    pub cdylib_names: BTreeMap<String, String>,
+}
+
+/// Return the path to the wasm file cargo produces for the given package.
+pub fn wasm_path(metadata: &CargoMetadata, package: &str, profile: &str) -> PathBuf {
+    let name = metadata
+        .cdylib_names
+        .get(package)
+        .map(|name| name.as_str())
+        .unwrap_or(package);
+
+    metadata
+        .target_dir
+        .join(WASM_TARGET)
+        .join(profile)
+        .join(format!("{}.wasm", name.replace('-', "_")))
+}
+
+/// Return the cargo metadata of the workspace that contains the given directory, falls back
+/// to `<dir>/target` and the package names if cargo could not be used.
+pub fn metadata(dir: &Path) -> CargoMetadata {
+    match get_metadata(dir) {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            log::debug!("Could not get the cargo metadata: {:?}", e);
+            CargoMetadata {
+                target_dir: dir.join("target"),
+                cdylib_names: BTreeMap::new(),
+            }
+        }
+    }
+}
+
+fn get_metadata(dir: &Path) -> Result<CargoMetadata> {
+    let output = Command::new("cargo")
+        .args(&["metadata", "--no-deps", "--format-version", "1"])
+        .current_dir(dir)
+        .output()
+        .context("Could not execute cargo metadata.")?;
+
+    if !output.status.success() {
+        bail!(
+            "cargo metadata failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let metadata: serde_json::Value =
+        serde_json::from_slice(&output.stdout).context("Could not parse cargo metadata.")?;
+
+    parse_metadata(&metadata)
+}
+
+fn parse_metadata(metadata: &serde_json::Value) -> Result<CargoMetadata> {
+    let target_dir = metadata["target_directory"]
+        .as_str()
+        .map(PathBuf::from)
+        .context("cargo metadata did not return a target directory.")?;
+
+    let mut cdylib_names = BTreeMap::new();
+    for package in metadata["packages"].as_array().into_iter().flatten() {
+        let targets = package["targets"].as_array().into_iter().flatten();
+
+        for target in targets {
+            let is_cdylib = target["kind"]
+                .as_array()
+                .map_or(false, |kind| kind.iter().any(|k| k == "cdylib"));
+
+            if let (true, Some(package), Some(name)) =
+                (is_cdylib, package["name"].as_str(), target["name"].as_str())
+            {
+                cdylib_names.insert(package.to_owned(), name.to_owned());
+            }
+        }
+    }
+
+    Ok(CargoMetadata {
+        target_dir,
+        cdylib_names,
+    })
+}
+
+/// Return the version of the Rust compiler, if it is available.
+pub fn compiler_version() -> Option<String> {
+    let output = Command::new("rustc").arg("--version").output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+/// Make sure the wasm32 target is installed, if rustup is not available we let cargo report
+/// the error instead.
+pub fn check_wasm_target() -> Result<()> {
+    let output = match Command::new("rustup")
+        .args(&["target", "list", "--installed"])
+        .output()
+    {
+        Ok(output) if output.status.success() => output,
+        _ => {
+            log::debug!("Could not use rustup to check the installed targets.");
+            return Ok(());
+        }
+    };
+
+    let installed = String::from_utf8_lossy(&output.stdout);
+    if !installed.lines().any(|line| line.trim() == WASM_TARGET) {
+        bail!(
+            "The {} target is not installed. Run 'rustup target add {}' first.",
+            WASM_TARGET,
+            WASM_TARGET
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wasm_name_from_metadata() {
+        let metadata = serde_json::json!({
+            "target_directory": "/ws/target",
+            "packages": [
+                {
+                    "name": "my-backend",
+                    "targets": [
+                        { "name": "backend_lib", "kind": ["cdylib"] },
+                        { "name": "my-backend", "kind": ["bin"] }
+                    ]
+                },
+                {
+                    "name": "my-frontend",
+                    "targets": [{ "name": "my-frontend", "kind": ["lib", "cdylib"] }]
+                }
+            ]
+        });
+
+        let metadata = parse_metadata(&metadata).unwrap();
+        assert_eq!(
+            wasm_path(&metadata, "my-backend", "release"),
+            PathBuf::from("/ws/target/wasm32-unknown-unknown/release/backend_lib.wasm")
+        );
+        assert_eq!(
+            wasm_path(&metadata, "my-frontend", "debug"),
+            PathBuf::from("/ws/target/wasm32-unknown-unknown/debug/my_frontend.wasm")
+        );
+        assert_eq!(
+            wasm_path(&metadata, "other", "release"),
+            PathBuf::from("/ws/target/wasm32-unknown-unknown/release/other.wasm")
+        );
+    }
+}
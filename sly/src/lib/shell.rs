@@ -0,0 +1,111 @@
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use anyhow::{bail, Context, Result};
+
+/// Run the given command using `sh -c` in the given directory and wait for it to finish.
+///
+/// The output of the command is captured, and in case the command fails it is included
+/// in the returned error.
+pub fn run_captured<I, K, V>(command: &str, cwd: &Path, envs: I) -> Result<()>
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<std::ffi::OsStr>,
+    V: AsRef<std::ffi::OsStr>,
+{
+    log::trace!("Running '{}' in {:?}", command, cwd);
+
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .current_dir(cwd)
+        .envs(envs)
+        .output()
+        .with_context(|| format!("Could not execute command '{}'", command))?;
+
+    if !output.status.success() {
+        let mut message = format!("Command '{}' failed with {}.", command, output.status);
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if !stdout.trim().is_empty() {
+            message.push_str(&format!("\nstdout:\n{}", stdout.trim_end()));
+        }
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !stderr.trim().is_empty() {
+            message.push_str(&format!("\nstderr:\n{}", stderr.trim_end()));
+        }
+
+        bail!(message);
+    }
+
+    Ok(())
+}
+
+/// Run the given command using `sh -c` in the given directory and wait for it to finish,
+/// every line of its output is printed with the given prefix.
+pub fn run_prefixed<I, K, V>(command: &str, cwd: &Path, envs: I, prefix: &str) -> Result<()>
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<std::ffi::OsStr>,
+    V: AsRef<std::ffi::OsStr>,
+{
+    log::trace!("Running '{}' in {:?}", command, cwd);
+
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .current_dir(cwd)
+        .envs(envs)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Could not execute command '{}'", command))?;
+
+    let stdout = child.stdout.take().unwrap();
+    let stderr = child.stderr.take().unwrap();
+
+    let status = crossbeam::scope(|s| {
+        s.spawn(|_| print_lines(stdout, prefix, false));
+        s.spawn(|_| print_lines(stderr, prefix, true));
+        child.wait()
+    })
+    .unwrap()
+    .with_context(|| format!("Failed to wait for command '{}'", command))?;
+
+    if !status.success() {
+        bail!("Command '{}' failed with {}.", command, status);
+    }
+
+    Ok(())
+}
+
+fn print_lines<R: Read>(reader: R, prefix: &str, is_stderr: bool) {
+    for line in BufReader::new(reader).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+
+        if is_stderr {
+            eprintln!("{} {}", prefix, line);
+        } else {
+            println!("{} {}", prefix, line);
+        }
+    }
+}
+
+/// Quote the given string so it is passed as a single word to `sh`.
+pub fn quote(value: &str) -> String {
+    let is_safe = !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:@+,".contains(c));
+
+    if is_safe {
+        value.to_owned()
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
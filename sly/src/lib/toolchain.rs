@@ -1,5 +1,5 @@
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use anyhow::{bail, Context, Result};
 
@@ -23,19 +23,19 @@ pub fn get_binary_command_path(binary_name: &str) -> Result<PathBuf> {
     Ok(path)
 }
 
-/// Return the file that ic-starter should write its port to.
-pub fn get_replica_port_file() -> Result<PathBuf> {
-    Ok(get_replica_state_root()?.join("replica-port"))
+/// Return the file that ic-starter should write its port to, for the replica whose data is
+/// stored in the given root.
+pub fn get_replica_port_file(root: &Path) -> PathBuf {
+    root.join("replica-port")
 }
 
 /// Return the file that replica actor uses to store the ic-starter's pid.
-pub fn get_replica_pid_file() -> Result<PathBuf> {
-    Ok(get_replica_state_root()?.join("replica-pid"))
+pub fn get_replica_pid_file(root: &Path) -> PathBuf {
+    root.join("replica-pid")
 }
 
 /// The directory that is used by the ic-starter to store the replicated_state.
-pub fn get_replica_state_directory() -> Result<PathBuf> {
-    let root = get_replica_state_root()?;
+pub fn get_replica_state_directory(root: &Path) -> Result<PathBuf> {
     let state_directory = root.join("state");
 
     if !state_directory.exists() {
@@ -50,10 +50,10 @@ pub fn get_replica_state_directory() -> Result<PathBuf> {
     Ok(state_directory)
 }
 
-/// Return the directory that is used by the replica to store the state and pid/port files.
-/// This method ensures that the directory does exists and creates the directory in case it
-/// does not exists.
-fn get_replica_state_root() -> Result<PathBuf> {
+/// Return the directory that is used by the local replica to store the state and pid/port
+/// files. This method ensures that the directory does exists and creates the directory in case
+/// it does not exists.
+pub fn get_replica_state_root() -> Result<PathBuf> {
     let data_dir = dirs::data_dir().context("Can not get the data directory.")?;
     let root = data_dir.join("psychedelic").join("replica");
 
@@ -1,3 +1,6 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
 use std::str::FromStr;
 
 use anyhow::{bail, Context, Result};
@@ -7,6 +10,39 @@ use ic_utils::interfaces::management_canister::{
     MgmtMethod,
 };
 
+/// Write the content to a temporary file and then rename it to the actual path, the rename
+/// is atomic, so readers either see the old or the new content.
+pub fn write_atomic(path: &Path, content: &[u8]) -> Result<()> {
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let mut file =
+        fs::File::create(&tmp_path).with_context(|| format!("Failed to create {:?}", tmp_path))?;
+    file.write_all(content)
+        .with_context(|| format!("Failed to write {:?}", tmp_path))?;
+    file.sync_all()
+        .with_context(|| format!("Failed to flush {:?}", tmp_path))?;
+
+    fs::rename(&tmp_path, path).with_context(|| format!("Failed to replace {:?}", path))
+}
+
+/// Convert the name of a network to something that can be used as a file name, networks
+/// can be raw URLs.
+pub fn network_file_name(network: &str) -> String {
+    network
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// Compute the sha256 hash of the given data.
+pub fn sha256(data: &[u8]) -> Vec<u8> {
+    ring::digest::digest(&ring::digest::SHA256, data)
+        .as_ref()
+        .to_vec()
+}
+
 /// Flatten a result, because .flatten() is unstable.
 #[inline]
 pub fn result_flatten<T, E>(result: Result<Result<T, E>, E>) -> Result<T, E> {
@@ -70,3 +106,69 @@ pub fn get_effective_canister_id(
         }
     }
 }
+
+/// Parse an amount of cycles, the amount can be a plain number or use one of the `K`, `M`,
+/// `B` or `T` suffixes, e.g `2.5T` is parsed as `2_500_000_000_000`.
+pub fn parse_cycles(amount: &str) -> Result<u64, String> {
+    let amount = amount.trim().replace('_', "");
+    let (number, multiplier) = match amount.chars().last() {
+        Some('K') | Some('k') => (&amount[..amount.len() - 1], 1_000u64),
+        Some('M') | Some('m') => (&amount[..amount.len() - 1], 1_000_000),
+        Some('B') | Some('b') => (&amount[..amount.len() - 1], 1_000_000_000),
+        Some('T') | Some('t') => (&amount[..amount.len() - 1], 1_000_000_000_000),
+        _ => (amount.as_str(), 1),
+    };
+
+    if let Ok(value) = number.parse::<u64>() {
+        return value
+            .checked_mul(multiplier)
+            .ok_or_else(|| format!("Amount of cycles is too large: {}", amount));
+    }
+
+    match number.parse::<f64>() {
+        Ok(value) if value >= 0.0 && (value * multiplier as f64) < u64::MAX as f64 => {
+            Ok((value * multiplier as f64) as u64)
+        }
+        _ => Err(format!("Invalid amount of cycles: {}", amount)),
+    }
+}
+
+/// Replace the `${VAR}` references in the given string using `lookup`, references to unknown
+/// variables are left as they are.
+pub fn interpolate<F>(input: &str, lookup: F) -> String
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut result = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(start) = rest.find("${") {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+
+        match after.find('}') {
+            Some(end) => {
+                let name = &after[..end];
+                match lookup(name) {
+                    Some(value) => result.push_str(&value),
+                    None => result.push_str(&rest[start..start + end + 3]),
+                }
+                rest = &after[end + 1..];
+            }
+            None => {
+                result.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+
+    result.push_str(rest);
+    result
+}
+
+/// Return the name of the first `${VAR}` reference in the string, if any.
+pub fn find_variable_reference(input: &str) -> Option<&str> {
+    let start = input.find("${")? + 2;
+    let end = input[start..].find('}')?;
+    Some(&input[start..start + end])
+}
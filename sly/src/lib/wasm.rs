@@ -0,0 +1,140 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use humansize::{file_size_opts, FileSize};
+
+/// The maximum size of an ingress message accepted by the Internet Computer.
+pub const MAX_INGRESS_MESSAGE_SIZE: usize = 2 * 1024 * 1024;
+
+/// The space we reserve in an install_code message for the request envelope, the
+/// candid encoding of the arguments and the signature.
+pub const ENVELOPE_OVERHEAD: usize = 4 * 1024;
+
+/// A wasm module that is ready to be sent to the network.
+pub struct WasmModule {
+    /// The bytes of the module as it should be installed, possibly gzipped.
+    pub bytes: Vec<u8>,
+    /// Size of the module before compression.
+    pub original_size: usize,
+    /// Whether the bytes are gzip compressed.
+    pub gzipped: bool,
+}
+
+impl WasmModule {
+    /// Load the wasm module from the given path, paths ending with `.gz` are expected to
+    /// already be gzip compressed, otherwise the module is compressed if `compress` is set.
+    pub fn load(path: &Path, compress: bool) -> Result<Self> {
+        let bytes = fs::read(path).with_context(|| format!("Could not read {:?}", path))?;
+
+        if is_gzip_path(path) {
+            if !bytes.starts_with(&[0x1f, 0x8b]) {
+                bail!("{:?} does not look like a gzip file.", path);
+            }
+
+            let original_size = bytes.len();
+            return Ok(Self {
+                bytes,
+                original_size,
+                gzipped: true,
+            });
+        }
+
+        let original_size = bytes.len();
+
+        if !compress {
+            return Ok(Self {
+                bytes,
+                original_size,
+                gzipped: false,
+            });
+        }
+
+        log::trace!("Compressing {:?}", path);
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+        encoder
+            .write_all(&bytes)
+            .with_context(|| format!("Failed to gzip {:?}", path))?;
+        let bytes = encoder
+            .finish()
+            .with_context(|| format!("Failed to gzip {:?}", path))?;
+
+        Ok(Self {
+            bytes,
+            original_size,
+            gzipped: true,
+        })
+    }
+
+    /// Make sure that an install_code message with this module and the given argument fits
+    /// in an ingress message, the returned error contains the size breakdown.
+    pub fn check_ingress_size(&self, canister: &str, arg_size: usize) -> Result<()> {
+        let total = self.bytes.len() + arg_size + ENVELOPE_OVERHEAD;
+
+        if total <= MAX_INGRESS_MESSAGE_SIZE {
+            return Ok(());
+        }
+
+        let mut message = format!(
+            "The install_code message for canister '{}' exceeds the ingress message size limit.\n",
+            canister
+        );
+
+        if self.gzipped {
+            message.push_str(&format!(
+                "  Wasm module:       {:>10} (gzip, {} uncompressed)\n",
+                display_size(self.bytes.len()),
+                display_size(self.original_size)
+            ));
+        } else {
+            message.push_str(&format!(
+                "  Wasm module:       {:>10}\n",
+                display_size(self.bytes.len())
+            ));
+        }
+
+        message.push_str(&format!(
+            "  Install argument:  {:>10}\n",
+            display_size(arg_size)
+        ));
+        message.push_str(&format!(
+            "  Envelope overhead: {:>10}\n",
+            display_size(ENVELOPE_OVERHEAD)
+        ));
+        message.push_str(&format!(
+            "  Total:             {:>10}\n",
+            display_size(total)
+        ));
+        message.push_str(&format!(
+            "  Limit:             {:>10}\n",
+            display_size(MAX_INGRESS_MESSAGE_SIZE)
+        ));
+
+        if !self.gzipped {
+            message.push_str("Try setting \"compress\": true for the canister in sly.json.");
+        } else {
+            message.push_str("Try optimizing the wasm with 'sly wasm optimize'.");
+        }
+
+        bail!(message)
+    }
+}
+
+/// Returns true if the path points to a gzipped file.
+pub fn is_gzip_path(path: &Path) -> bool {
+    path.extension().map_or(false, |ext| ext == "gz")
+}
+
+fn display_size(size: usize) -> String {
+    size.file_size(file_size_opts::BINARY).unwrap()
+}
+
+/// Return the path where the optimized version of the given wasm is written to, i.e
+/// `canister.wasm` becomes `canister.opt.wasm`.
+pub fn optimized_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}.opt.wasm", stem))
+}
@@ -1,9 +1,24 @@
-use anyhow::{bail, Context};
+use anyhow::{anyhow, bail, Context};
+use candid::Principal;
 use serde::{Deserialize, Serialize};
 use std::borrow::Borrow;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+use crate::lib::manifest_error::{self, ManifestError, PathSegment, SourceFile};
+use crate::lib::network::Network;
+use crate::lib::{dotenv, motoko, rust, toolchain, utils, wasm};
+use crate::wasm_optimizer;
+
+/// The name of the manifest file that is used in the diagnostics.
+const MANIFEST_NAME: &str = "sly.json";
+
+/// The JSON schema of the manifest.
+/// The prefix of the variables that sly sets for the commands it runs.
+const COMMAND_VAR_PREFIX: &str = "SLY_";
+
+pub static MANIFEST_SCHEMA: &str = include_str!("../../sly.schema.json");
 
 /// Holds the information gathered from parsing Sly.json
 #[derive(Clone)]
@@ -13,38 +28,299 @@ pub struct Workspace {
     pub root: PathBuf,
     /// The list of canisters.
     pub canisters: BTreeMap<String, Canister>,
+    /// The Motoko packages, mapping the name of each package to its path.
+    pub packages: BTreeMap<String, String>,
+    /// The variables loaded from the `.env` file in the root directory, excluding the ones
+    /// that are overridden by the environment.
+    pub dotenv: BTreeMap<String, String>,
+    /// The scripts that can be executed using `sly run`.
+    pub scripts: BTreeMap<String, Script>,
+    /// The networks that can be used by name, including the predefined `ic` and `local`.
+    pub networks: BTreeMap<String, Network>,
+    /// The manifest after merging the extended manifests and the profile, the variables are
+    /// left as is so the values of `.env` are not exposed.
+    pub manifest: serde_json::Value,
 }
 
 #[derive(Debug, Clone)]
 pub struct Canister {
+    pub kind: CanisterKind,
     pub build: BTreeMap<String, Vec<String>>,
     pub test: BTreeMap<String, Vec<String>>,
     pub wasm: BTreeMap<String, String>,
     pub candid: BTreeMap<String, String>,
+    pub dependencies: Vec<String>,
+    /// Glob patterns of the files the build depends on, relative to the workspace root.
+    pub inputs: Vec<String>,
+    pub compress: bool,
+    /// How the wasm should be optimized after the build for each mode, `None` disables the
+    /// optimization.
+    pub optimize: BTreeMap<String, Option<OptimizeSettings>>,
+    pub hooks: CanisterHooks,
+    /// The controllers of the canister on each network, as identity names or principal ids.
+    pub controllers: BTreeMap<String, Vec<String>>,
+}
+
+/// The settings of the optimization that runs on the wasm of a canister after the build.
+#[derive(Debug, Clone)]
+pub struct OptimizeSettings {
+    /// Names of the optimization passes to run in order, all of the passes are used if not set.
+    pub passes: Option<Vec<String>>,
+    /// The binaryen optimization level.
+    pub level: u32,
+}
+
+impl Default for OptimizeSettings {
+    fn default() -> Self {
+        Self {
+            passes: None,
+            level: wasm_optimizer::DEFAULT_LEVEL,
+        }
+    }
+}
+
+/// The kind of a canister, which determines how sly deploys it.
+#[derive(Debug, Clone)]
+pub enum CanisterKind {
+    /// A canister that is built using the build commands in sly.json.
+    Custom,
+    /// A Rust canister, the build command and the wasm path are derived from the package.
+    Rust {
+        /// Name of the cargo package.
+        package: String,
+    },
+    /// A Motoko canister that is compiled using the toolchain's moc.
+    Motoko {
+        /// Path to the main Motoko file of the canister.
+        main: String,
+    },
+    /// A canister that serves the files in the source directories, using the assets canister
+    /// that ships with the toolchain.
+    Assets {
+        /// The directories that are uploaded, relative to the workspace root.
+        source: Vec<String>,
+    },
+    /// A canister that is not developed in this workspace, e.g the ledger.
+    Remote {
+        /// The id of the canister on each network.
+        ids: BTreeMap<String, Principal>,
+    },
+}
+
+impl Canister {
+    /// Return the id of the canister on the given network if it is a remote canister.
+    pub fn remote_id(&self, network: &str) -> Option<Principal> {
+        match &self.kind {
+            CanisterKind::Remote { ids } => ids.get(network).cloned(),
+            _ => None,
+        }
+    }
+
+    /// Return the controllers of the canister on the given network, if the network does
+    /// not have its own list, the `default` one is used.
+    pub fn controllers(&self, network: &str) -> Option<&[String]> {
+        self.controllers
+            .get(network)
+            .or_else(|| self.controllers.get("default"))
+            .map(|x| x.as_slice())
+    }
+
+    /// Return the optimization settings of the canister for the given mode, if the mode does
+    /// not have its own settings the `default` ones are used.
+    pub fn optimize_settings(&self, mode: &str) -> Option<&OptimizeSettings> {
+        self.optimize
+            .get(mode)
+            .or_else(|| self.optimize.get("default"))
+            .and_then(|x| x.as_ref())
+    }
+
+    /// Fill the build commands and the wasm paths of a Rust canister for each mode, unless
+    /// they are set in sly.json.
+    fn derive_rust_settings(&mut self, metadata: Option<&rust::CargoMetadata>) {
+        let package = match &self.kind {
+            CanisterKind::Rust { package } => package.clone(),
+            _ => return,
+        };
+
+        let derive_build = self.build.is_empty();
+        let derive_wasm = self.wasm.is_empty();
+
+        for mode in &["default", "release", "debug"] {
+            let profile = rust::profile_for_mode(mode);
+
+            if derive_build {
+                self.build.insert(
+                    mode.to_string(),
+                    vec![rust::build_command(&package, profile)],
+                );
+            }
+
+            if let (true, Some(metadata)) = (derive_wasm, metadata) {
+                let path = rust::wasm_path(metadata, &package, profile);
+                self.wasm
+                    .insert(mode.to_string(), path.to_string_lossy().to_string());
+            }
+        }
+    }
+
+    /// Fill the build commands, the wasm and the candid paths of a Motoko canister for each
+    /// mode, unless they are set in sly.json.
+    fn derive_motoko_settings(
+        &mut self,
+        name: &str,
+        moc: &str,
+        packages: &BTreeMap<String, String>,
+    ) {
+        let main = match &self.kind {
+            CanisterKind::Motoko { main } => main.clone(),
+            _ => return,
+        };
+
+        let derive_build = self.build.is_empty();
+        let derive_wasm = self.wasm.is_empty();
+        let derive_candid = self.candid.is_empty();
+
+        for mode in &["default", "release", "debug"] {
+            let output_dir = motoko::output_dir(name, mode);
+            let output = output_dir.join(name).to_string_lossy().to_string();
+
+            if derive_build {
+                self.build.insert(
+                    mode.to_string(),
+                    motoko::build_commands(moc, &main, &output_dir, name, packages, mode),
+                );
+            }
+
+            if derive_wasm {
+                self.wasm
+                    .insert(mode.to_string(), format!("{}.wasm", output));
+            }
+
+            if derive_candid {
+                self.candid
+                    .insert(mode.to_string(), format!("{}.did", output));
+            }
+        }
+    }
+
+    /// Use the assets canister from the toolchain for an assets canister, unless the wasm and
+    /// candid are set in sly.json. The build commands are optional for assets canisters.
+    fn derive_assets_settings(
+        &mut self,
+        toolchain_wasm: Option<&Path>,
+        toolchain_candid: Option<&Path>,
+    ) {
+        if !matches!(self.kind, CanisterKind::Assets { .. }) {
+            return;
+        }
+
+        let derive_build = self.build.is_empty();
+        let derive_wasm = self.wasm.is_empty();
+        let derive_candid = self.candid.is_empty();
+
+        for mode in &["default", "release", "debug"] {
+            if derive_build {
+                self.build.insert(mode.to_string(), Vec::new());
+            }
+
+            if let (true, Some(path)) = (derive_wasm, toolchain_wasm) {
+                self.wasm
+                    .insert(mode.to_string(), path.to_string_lossy().to_string());
+            }
+
+            if let (true, Some(path)) = (derive_candid, toolchain_candid) {
+                self.candid
+                    .insert(mode.to_string(), path.to_string_lossy().to_string());
+            }
+        }
+    }
+
+    /// A remote canister is deployed from a prebuilt stand-in wasm on ephemeral networks, so
+    /// the build commands are optional.
+    fn derive_remote_settings(&mut self) {
+        if !matches!(self.kind, CanisterKind::Remote { .. }) || !self.build.is_empty() {
+            return;
+        }
+
+        let modes = ["default", "release", "debug"]
+            .iter()
+            .map(|mode| mode.to_string())
+            .chain(self.wasm.keys().cloned())
+            .collect::<Vec<_>>();
+
+        for mode in modes {
+            self.build.insert(mode, Vec::new());
+        }
+    }
+
+    /// Returns true if sly should not create, build or install this canister on the network
+    /// with the given name. On ephemeral networks a remote canister without an id can be
+    /// deployed from a stand-in wasm instead.
+    pub fn is_remote_on(&self, name: &str, network: &Network) -> bool {
+        match &self.kind {
+            CanisterKind::Remote { ids } => {
+                ids.contains_key(name) || !network.is_ephemeral() || self.wasm.is_empty()
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The commands that should be executed around the deploy steps of a canister.
+#[derive(Debug, Clone, Default)]
+pub struct CanisterHooks {
+    pub pre_build: Vec<String>,
+    pub post_build: Vec<String>,
+    pub pre_install: Vec<String>,
+    pub post_install: Vec<String>,
+}
+
+/// A task of the workspace that is executed using `sly run`.
+#[derive(Debug, Clone, Default)]
+pub struct Script {
+    pub commands: Vec<String>,
+    /// The scripts that should be executed before this one.
+    pub dependencies: Vec<String>,
+    /// The network used by the script, unless one is given in the command line.
+    pub network: Option<String>,
+    /// The identity used by the script, unless one is given in the command line.
+    pub identity: Option<String>,
 }
 
 impl Workspace {
-    pub fn from_current_directory() -> anyhow::Result<Self> {
+    pub fn from_current_directory(profile: Option<&str>) -> anyhow::Result<Self> {
+        let path = match Self::find_manifest()? {
+            Some(path) => path,
+            None => bail!("No sly.json found in the current path."),
+        };
+
+        let root = path.parent().unwrap().into();
+        let reader = std::fs::File::open(path.clone())
+            .with_context(|| format!("Failed to open file '{}'", path.to_string_lossy()))?;
+
+        Self::from_reader_with_profile(root, reader, profile)
+    }
+
+    /// Return the path of the sly.json in the current directory or the closest of its
+    /// parents, `None` if there is no such file.
+    pub fn find_manifest() -> anyhow::Result<Option<PathBuf>> {
         let cwd = env::current_dir().context("Failed to retrieve current working directory.")?;
         let mut dir = Some(cwd.as_path());
 
         while let Some(root) = dir {
-            let path = root.join("sly.json");
+            let path = root.join(MANIFEST_NAME);
 
             if path.is_file() {
-                let reader = std::fs::File::open(path.clone())
-                    .with_context(|| format!("Failed to open file '{}'", path.to_string_lossy()))?;
-
-                return Self::from_reader(root.into(), reader);
+                return Ok(Some(path));
             }
 
             dir = root.parent();
         }
 
-        bail!("No sly.json found in the current path.")
+        Ok(None)
     }
 
-    pub fn from_config_path(path: PathBuf) -> anyhow::Result<Self> {
+    pub fn from_config_path(path: PathBuf, profile: Option<&str>) -> anyhow::Result<Self> {
         if !path.is_file() {
             bail!("'{}' is not a file.", path.to_string_lossy())
         }
@@ -53,7 +329,7 @@ impl Workspace {
         let reader = std::fs::File::open(path.clone())
             .with_context(|| format!("Failed to open file '{}'", path.to_string_lossy()))?;
 
-        Self::from_reader(root, reader)
+        Self::from_reader_with_profile(root, reader, profile)
     }
 
     /// Create a workspace from a Sly.json that is located in the given `root` directory.
@@ -61,17 +337,245 @@ impl Workspace {
     where
         R: std::io::Read,
     {
-        let manifest = serde_json::from_reader::<_, manifest::Manifest>(reader)
-            .context("Could not parse the workspace manifest file.")?;
+        Self::from_reader_with_profile(root, reader, None)
+    }
 
-        let canisters = manifest
+    /// Create a workspace from a Sly.json that is located in the given `root` directory, and
+    /// apply the overrides of the given profile.
+    pub fn from_reader_with_profile<R>(
+        root: PathBuf,
+        mut reader: R,
+        profile: Option<&str>,
+    ) -> anyhow::Result<Self>
+    where
+        R: std::io::Read,
+    {
+        let mut dotenv = dotenv::load(&root.join(".env"))?;
+        dotenv.retain(|key, _| env::var_os(key).is_none());
+
+        let mut source = String::new();
+        reader
+            .read_to_string(&mut source)
+            .context("Could not read the workspace manifest file.")?;
+
+        let mut stack = vec![root.join(MANIFEST_NAME)];
+        let mut files = Vec::new();
+        let mut value = resolve_extends(&root, MANIFEST_NAME, &source, &mut stack, &mut files)?;
+
+        let profiles = value.as_object_mut().and_then(|m| m.remove("profiles"));
+        if let Some(profile) = profile {
+            match profiles.as_ref().and_then(|p| p.get(profile)) {
+                Some(overrides) => merge_manifest(&mut value, overrides.clone()),
+                None => {
+                    let names = profiles
+                        .as_ref()
+                        .and_then(|p| p.as_object())
+                        .map(|p| p.keys().cloned().collect::<Vec<_>>())
+                        .unwrap_or_default();
+                    bail!(
+                        "Profile '{}' is not defined in sly.json. Use --profile=[{}]",
+                        profile,
+                        names.join("/")
+                    );
+                }
+            }
+        }
+
+        let merged = value.clone();
+        interpolate_value(&mut value, &dotenv);
+
+        // Track the path of the value that fails, so the error can point at it in the source,
+        // the value might come from the overrides of the profile or from an extended manifest.
+        let interpolated = value.clone();
+        let manifest: manifest::Manifest =
+            serde_path_to_error::deserialize(value).map_err(|e| {
+                let path = manifest_error::path_segments(e.path());
+                let (path, error) = match explain_untagged(&interpolated, &path) {
+                    Some((path, error)) => (path, error),
+                    None => (path, e.into_inner()),
+                };
+                let mut candidates = Vec::new();
+
+                if let Some(profile) = profile {
+                    let mut in_profile = vec!["profiles".into(), profile.into()];
+                    in_profile.extend(path.iter().cloned());
+                    candidates.push(in_profile);
+                }
+
+                candidates.push(path.clone());
+                ManifestError::invalid(&files, &path, &candidates, &error)
+            })?;
+
+        let invalid = |path: &[&str], message: String| {
+            let path = path.iter().map(|&x| x.into()).collect::<Vec<PathSegment>>();
+            ManifestError::at(&files, &path, message)
+        };
+
+        let mut canisters: BTreeMap<String, Canister> = manifest
             .canisters
             .unwrap_or_default()
             .into_iter()
             .map(|(k, v)| (k, v.into()))
             .collect();
 
-        Ok(Self { root, canisters })
+        for (name, canister) in &canisters {
+            let path = ["canisters", name.as_str()];
+            let message = match &canister.kind {
+                CanisterKind::Rust { package } if package.is_empty() => Some(format!(
+                    "Rust canister '{}' does not specify a package.",
+                    name
+                )),
+                CanisterKind::Motoko { main } if main.is_empty() => Some(format!(
+                    "Motoko canister '{}' does not specify a main file.",
+                    name
+                )),
+                CanisterKind::Assets { source } if source.is_empty() => Some(format!(
+                    "Assets canister '{}' does not specify a source.",
+                    name
+                )),
+                _ => None,
+            };
+
+            if let Some(message) = message {
+                return Err(invalid(&path, message).into());
+            }
+
+            // Unknown variables are left for the shell in commands, but paths must resolve. The
+            // paths are resolved once for every mode and network, so they can not use the
+            // variables that sly only sets for the commands.
+            let paths = canister
+                .wasm
+                .values()
+                .chain(canister.candid.values())
+                .chain(canister.inputs.iter());
+
+            for path in paths {
+                if let Some(var) = utils::find_variable_reference(path) {
+                    let message = if var.starts_with(COMMAND_VAR_PREFIX) {
+                        format!(
+                            "Canister '{}' uses '{}' in path '{}', but the {}* variables are only set for the commands. Paths can only use the variables of the environment and .env.",
+                            name, var, path, COMMAND_VAR_PREFIX
+                        )
+                    } else {
+                        format!(
+                            "Canister '{}' uses the undefined variable '{}' in path '{}'.",
+                            name, var, path
+                        )
+                    };
+                    return Err(invalid(&["canisters", name.as_str()], message).into());
+                }
+            }
+        }
+
+        // Only ask cargo for the metadata if there is a Rust canister whose wasm is derived.
+        let needs_metadata = canisters.values().any(|c| match &c.kind {
+            CanisterKind::Rust { .. } => c.wasm.is_empty(),
+            _ => false,
+        });
+
+        let cargo_metadata = if needs_metadata {
+            Some(rust::metadata(&root))
+        } else {
+            None
+        };
+
+        for canister in canisters.values_mut() {
+            canister.derive_rust_settings(cargo_metadata.as_ref());
+            canister.derive_remote_settings();
+        }
+
+        let packages = manifest.packages.unwrap_or_default();
+        let has_motoko = canisters
+            .values()
+            .any(|c| matches!(c.kind, CanisterKind::Motoko { .. }));
+
+        if has_motoko {
+            let moc = motoko::moc_path();
+
+            // The base library is provided by the toolchain, unless it is overridden.
+            let mut moc_packages = packages.clone();
+            if !moc_packages.contains_key("base") {
+                if let Some(base) = motoko::base_library_path() {
+                    moc_packages.insert("base".into(), base.to_string_lossy().to_string());
+                }
+            }
+
+            for (name, canister) in canisters.iter_mut() {
+                canister.derive_motoko_settings(name, &moc, &moc_packages);
+            }
+        }
+
+        let has_assets = canisters
+            .values()
+            .any(|c| matches!(c.kind, CanisterKind::Assets { .. }));
+
+        if has_assets {
+            let wasm = toolchain::get_binary_command_path("assetstorage.wasm").ok();
+            let candid = toolchain::get_binary_command_path("assetstorage.did").ok();
+
+            for canister in canisters.values_mut() {
+                canister.derive_assets_settings(wasm.as_deref(), candid.as_deref());
+            }
+        }
+
+        let mut networks = BTreeMap::new();
+        for name in &["ic", "local"] {
+            networks.insert(name.to_string(), Network::predefined(name).unwrap());
+        }
+
+        for (name, info) in manifest.networks.unwrap_or_default() {
+            let base = match (Network::predefined(&name), &info.url) {
+                (Some(network), _) => network,
+                (None, Some(url)) => Network::from_url(url),
+                (None, None) => {
+                    let message = format!("Network '{}' does not specify a url.", name);
+                    return Err(invalid(&["networks", name.as_str()], message).into());
+                }
+            };
+
+            networks.insert(name, info.apply(base));
+        }
+
+        let scripts = manifest
+            .scripts
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(k, v)| (k, v.into()))
+            .collect();
+
+        Ok(Self {
+            root,
+            canisters,
+            packages,
+            dotenv,
+            scripts,
+            networks,
+            manifest: merged,
+        })
+    }
+
+    /// Remove the canisters that sly does not deploy on the given network from the list.
+    pub fn filter_deployable(
+        &self,
+        names: Vec<String>,
+        network_name: &str,
+        network: &Network,
+    ) -> Vec<String> {
+        names
+            .into_iter()
+            .filter(|name| match self.get_canister(name) {
+                Some(canister) if canister.is_remote_on(network_name, network) => {
+                    log::debug!("Skipping remote canister '{}'.", name);
+                    false
+                }
+                _ => true,
+            })
+            .collect()
+    }
+
+    /// Return the directory that sly uses to store its data for this workspace.
+    pub fn sly_dir(&self) -> PathBuf {
+        self.root.join(".sly")
     }
 
     /// Return the settings for a canister.
@@ -82,13 +586,390 @@ impl Workspace {
     {
         self.canisters.get(name)
     }
+
+    /// Return the absolute path to the wasm that should be installed for a canister, this is
+    /// the optimized module for canisters that are optimized after the build.
+    pub fn get_wasm_path(&self, name: &str, mode: &str) -> Option<PathBuf> {
+        let canister = self.get_canister(name)?;
+        let path = self.root.join(canister.wasm.get(mode)?);
+
+        if canister.optimize_settings(mode).is_some() && !wasm::is_gzip_path(&path) {
+            Some(wasm::optimized_path(&path))
+        } else {
+            Some(path)
+        }
+    }
+
+    /// Return the absolute path to the candid file of a canister, if the canister does
+    /// not have a candid file for the given mode, the one for the `default` mode is used.
+    pub fn get_candid_path(&self, name: &str, mode: &str) -> Option<PathBuf> {
+        let candid = &self.get_canister(name)?.candid;
+        let path = candid.get(mode).or_else(|| candid.get("default"))?;
+        Some(self.root.join(path))
+    }
+
+    /// Sort the given canisters into levels so that every canister comes after the canisters
+    /// it depends on, canisters in the same level do not depend on each other.
+    ///
+    /// Dependencies that are not in `names` are not included in the result, but they are
+    /// still checked for circular references.
+    pub fn topological_levels(&self, names: &[String]) -> anyhow::Result<Vec<Vec<String>>> {
+        let mut depth = BTreeMap::<String, usize>::new();
+        let mut stack = Vec::new();
+
+        for name in names {
+            self.visit_dependencies(name, &mut depth, &mut stack)?;
+        }
+
+        let requested = names.iter().collect::<BTreeSet<_>>();
+
+        // Visit the canisters with lower depth first, so dependencies are placed before
+        // the canisters depending on them.
+        let mut sorted = requested.iter().cloned().collect::<Vec<_>>();
+        sorted.sort_by_key(|name| depth[*name]);
+
+        // Assign the levels ignoring the dependencies that are not requested.
+        let mut result: Vec<Vec<String>> = Vec::new();
+        let mut placed = BTreeMap::<&String, usize>::new();
+
+        for name in sorted {
+            let level = self.canisters[name]
+                .dependencies
+                .iter()
+                .filter(|dep| requested.contains(dep))
+                .map(|dep| placed[dep] + 1)
+                .max()
+                .unwrap_or(0);
+
+            if result.len() <= level {
+                result.resize_with(level + 1, Vec::new);
+            }
+
+            result[level].push(name.clone());
+            placed.insert(name, level);
+        }
+
+        Ok(result)
+    }
+
+    /// Return the given canisters sorted so that every canister comes after the canisters
+    /// it depends on.
+    pub fn topological_order(&self, names: &[String]) -> anyhow::Result<Vec<String>> {
+        Ok(self
+            .topological_levels(names)?
+            .into_iter()
+            .flatten()
+            .collect())
+    }
+
+    /// Return the scripts that should be executed to run the given script, every script
+    /// comes after the scripts it depends on and the given script is the last one.
+    pub fn script_order(&self, name: &str) -> anyhow::Result<Vec<String>> {
+        let mut result = Vec::new();
+        let mut stack = Vec::new();
+        self.visit_script(name, &mut result, &mut stack)?;
+        Ok(result)
+    }
+
+    fn visit_script(
+        &self,
+        name: &str,
+        result: &mut Vec<String>,
+        stack: &mut Vec<String>,
+    ) -> anyhow::Result<()> {
+        if result.iter().any(|n| n == name) {
+            return Ok(());
+        }
+
+        if let Some(index) = stack.iter().position(|n| n == name) {
+            let mut path = stack[index..].to_vec();
+            path.push(name.to_owned());
+            bail!("Circular dependency between scripts: {}", path.join(" -> "));
+        }
+
+        let script = match self.scripts.get(name) {
+            Some(script) => script,
+            None if stack.is_empty() => bail!("Script '{}' not found in sly.json.", name),
+            None => bail!(
+                "Script '{}' depends on '{}' which is not defined in sly.json.",
+                stack.last().unwrap(),
+                name
+            ),
+        };
+
+        stack.push(name.to_owned());
+
+        for dep in &script.dependencies {
+            self.visit_script(dep, result, stack)?;
+        }
+
+        stack.pop();
+        result.push(name.to_owned());
+
+        Ok(())
+    }
+
+    /// Compute the depth of a canister in the dependency graph using a DFS.
+    fn visit_dependencies(
+        &self,
+        name: &str,
+        depth: &mut BTreeMap<String, usize>,
+        stack: &mut Vec<String>,
+    ) -> anyhow::Result<usize> {
+        if let Some(d) = depth.get(name) {
+            return Ok(*d);
+        }
+
+        if let Some(index) = stack.iter().position(|n| n == name) {
+            let mut path = stack[index..].to_vec();
+            path.push(name.to_owned());
+            bail!(
+                "Circular dependency between canisters: {}",
+                path.join(" -> ")
+            );
+        }
+
+        let canister = self
+            .get_canister(name)
+            .ok_or_else(|| anyhow!("Canister '{}' not found.", name))?;
+
+        stack.push(name.to_owned());
+
+        let mut d = 0;
+        for dep in &canister.dependencies {
+            if self.get_canister(dep).is_none() {
+                bail!(
+                    "Canister '{}' depends on '{}' which is not defined in sly.json.",
+                    name,
+                    dep
+                );
+            }
+
+            d = d.max(self.visit_dependencies(dep, depth, stack)? + 1);
+        }
+
+        stack.pop();
+        depth.insert(name.to_owned(), d);
+
+        Ok(d)
+    }
+}
+
+/// Parse a manifest and merge it over the manifest it extends, if any. The paths in `extends`
+/// are relative to the directory of the manifest, `stack` holds the manifests that are being
+/// loaded to detect circular references. The sources of the manifests are added to `files`,
+/// the extending manifest first, so errors can be located in the file that caused them.
+fn resolve_extends(
+    dir: &Path,
+    name: &str,
+    source: &str,
+    stack: &mut Vec<PathBuf>,
+    files: &mut Vec<SourceFile>,
+) -> anyhow::Result<serde_json::Value> {
+    let mut value = serde_json::from_str::<serde_json::Value>(source)
+        .map_err(|e| ManifestError::syntax(name, source, &e))?;
+    files.push(SourceFile::new(name, source));
+
+    let extends = match value.as_object_mut().and_then(|m| m.remove("extends")) {
+        None => return Ok(value),
+        Some(serde_json::Value::String(extends)) => extends,
+        Some(_) => {
+            let message = "'extends' must be the path to a manifest.".to_string();
+            let file = SourceFile::new(name, source);
+            return Err(ManifestError::at(&[file], &["extends".into()], message).into());
+        }
+    };
+
+    let path = dir.join(&extends);
+    let canonical = path.canonicalize().with_context(|| {
+        format!(
+            "Failed to find '{}' which is extended by {}.",
+            extends, name
+        )
+    })?;
+
+    if stack
+        .iter()
+        .any(|p| p.canonicalize().ok().as_ref() == Some(&canonical))
+    {
+        bail!(
+            "Circular extends: {} extends '{}' which is already being loaded.",
+            name,
+            extends
+        );
+    }
+
+    let base_source = std::fs::read_to_string(&canonical)
+        .with_context(|| format!("Failed to read '{}'.", path.to_string_lossy()))?;
+
+    stack.push(canonical.clone());
+    let mut base = resolve_extends(
+        canonical.parent().unwrap(),
+        &path.to_string_lossy(),
+        &base_source,
+        stack,
+        files,
+    )?;
+    stack.pop();
+
+    merge_manifest(&mut base, value);
+    Ok(base)
+}
+
+/// The untagged values only report that none of their variants matched, so deserialize the
+/// failing value as its object variant alone to get the precise error, e.g an unknown field.
+/// Returns the path of the value that fails and the error.
+fn explain_untagged(
+    manifest: &serde_json::Value,
+    path: &[PathSegment],
+) -> Option<(Vec<PathSegment>, serde_json::Error)> {
+    let mut value = manifest;
+    for segment in path {
+        value = match segment {
+            PathSegment::Key(key) => value.get(key)?,
+            PathSegment::Index(index) => value.get(*index)?,
+        };
+    }
+
+    let keys = path
+        .iter()
+        .map(|segment| match segment {
+            PathSegment::Key(key) => key.as_str(),
+            PathSegment::Index(_) => "",
+        })
+        .collect::<Vec<_>>();
+
+    match keys.as_slice() {
+        ["scripts", _] if value.is_object() => {
+            let error = serde_json::from_value::<manifest::TaskInfo>(value.clone()).err()?;
+            Some((path.to_vec(), error))
+        }
+        ["canisters", _, "optimize"] => {
+            let object = value.as_object()?;
+
+            // The settings only have plain values, a map from the mode to the settings does not.
+            if !object.values().any(|v| v.is_object()) {
+                let error =
+                    serde_json::from_value::<manifest::OptimizeInfo>(value.clone()).err()?;
+                return Some((path.to_vec(), error));
+            }
+
+            object.iter().find_map(|(mode, settings)| {
+                let error = serde_json::from_value::<manifest::Optimize>(settings.clone()).err()?;
+                let error = match settings {
+                    serde_json::Value::Object(_) => {
+                        serde_json::from_value::<manifest::OptimizeInfo>(settings.clone()).err()?
+                    }
+                    _ => error,
+                };
+
+                let mut path = path.to_vec();
+                path.push(mode.as_str().into());
+                Some((path, error))
+            })
+        }
+        _ => None,
+    }
+}
+
+/// The fields of a canister that are either the same for every mode or a map from the mode
+/// to the value.
+const MODE_FIELDS: &[&str] = &["build", "test", "wasm", "candid", "optimize", "controllers"];
+
+/// Merge the overrides into a manifest. The plain value of a field that depends on the mode
+/// is the value of the `default` mode, so it is kept when the overrides only set some modes.
+fn merge_manifest(base: &mut serde_json::Value, overrides: serde_json::Value) {
+    let base_canisters = base.get_mut("canisters").and_then(|c| c.as_object_mut());
+    let canisters = overrides.get("canisters").and_then(|c| c.as_object());
+
+    if let (Some(base_canisters), Some(canisters)) = (base_canisters, canisters) {
+        for (name, canister) in canisters {
+            let base_canister = match base_canisters.get_mut(name) {
+                Some(serde_json::Value::Object(base_canister)) => base_canister,
+                _ => continue,
+            };
+
+            for field in MODE_FIELDS {
+                let (base_value, value) =
+                    match (base_canister.get_mut(*field), canister.get(*field)) {
+                        (Some(base_value), Some(value)) => (base_value, value),
+                        _ => continue,
+                    };
+
+                if is_mode_map(field, value) && !is_mode_map(field, base_value) {
+                    let default = base_value.take();
+                    *base_value = serde_json::json!({ "default": default });
+                }
+            }
+        }
+    }
+
+    merge_value(base, overrides);
+}
+
+/// Returns true if the value of the given field is a map from the mode to the value.
+fn is_mode_map(field: &str, value: &serde_json::Value) -> bool {
+    match value.as_object() {
+        // The settings of the optimization are an object as well.
+        Some(map) if field == "optimize" => !map.keys().all(|k| k == "passes" || k == "level"),
+        Some(_) => true,
+        None => false,
+    }
+}
+
+/// Merge the overrides into the base value, objects are merged recursively and any other
+/// value replaces the one in the base.
+fn merge_value(base: &mut serde_json::Value, overrides: serde_json::Value) {
+    match (base, overrides) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_value(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
+/// Replace the `${VAR}` references in all of the strings of a json value, the variables are
+/// looked up in the environment and then in the `.env` file. The `SLY_` variables are set by
+/// sly for the commands it runs, e.g `SLY_MODE` or `SLY_CANISTER_ID_<NAME>`, so they are
+/// left for the shell instead of being taken from the environment of sly itself.
+fn interpolate_value(value: &mut serde_json::Value, dotenv: &BTreeMap<String, String>) {
+    match value {
+        serde_json::Value::String(s) => {
+            *s = utils::interpolate(s, |name| {
+                if name.starts_with(COMMAND_VAR_PREFIX) {
+                    return None;
+                }
+                env::var(name).ok().or_else(|| dotenv.get(name).cloned())
+            });
+        }
+        serde_json::Value::Array(items) => {
+            for item in items {
+                interpolate_value(item, dotenv);
+            }
+        }
+        serde_json::Value::Object(map) => {
+            for item in map.values_mut() {
+                interpolate_value(item, dotenv);
+            }
+        }
+        _ => {}
+    }
 }
 
 mod manifest {
     use super::*;
+    use crate::lib::network::{NetworkKind, RootKeyPolicy};
 
     /// The schema for Sly.json files.
     #[derive(Serialize, Deserialize, Debug)]
+    #[serde(deny_unknown_fields)]
     pub struct Manifest {
         /// The version of the replica binary which should be used
         /// for this project.
@@ -96,15 +977,126 @@ mod manifest {
         /// List of the canisters that are developed under this
         /// project.
         pub canisters: Option<BTreeMap<String, CanisterInfo>>,
+        /// The Motoko packages used by the canisters, mapping the name to the path.
+        pub packages: Option<BTreeMap<String, String>>,
+        /// The tasks of the workspace that are executed using `sly run`.
+        pub scripts: Option<BTreeMap<String, ScriptInfo>>,
+        /// The networks of the workspace, the predefined `ic` and `local` networks can
+        /// also be customized here.
+        pub networks: Option<BTreeMap<String, NetworkInfo>>,
+    }
+
+    /// The settings of a network, the unset values are taken from the predefined network
+    /// with the same name or the defaults.
+    #[derive(Serialize, Deserialize, Debug)]
+    #[serde(deny_unknown_fields)]
+    pub struct NetworkInfo {
+        url: Option<String>,
+        #[serde(rename = "type")]
+        kind: Option<NetworkKind>,
+        root_key: Option<RootKeyPolicy>,
+        wallet: Option<Principal>,
+    }
+
+    impl NetworkInfo {
+        /// Override the settings of the given network with the ones that are set.
+        pub fn apply(self, mut network: Network) -> Network {
+            if self.url.is_some() {
+                network.url = self.url;
+            }
+
+            if let Some(kind) = self.kind {
+                network.kind = kind;
+            }
+
+            if let Some(root_key) = self.root_key {
+                network.root_key = root_key;
+            }
+
+            if self.wallet.is_some() {
+                network.wallet = self.wallet;
+            }
+
+            network
+        }
     }
 
     /// Information regarding a certain canister.
     #[derive(Serialize, Deserialize, Debug)]
+    #[serde(deny_unknown_fields)]
     pub struct CanisterInfo {
+        #[serde(rename = "type")]
+        kind: Option<CanisterType>,
+        remote: Option<BTreeMap<String, Principal>>,
+        package: Option<String>,
+        main: Option<String>,
+        source: Option<Vec<String>>,
         build: Option<WithMode<Command>>,
         test: Option<WithMode<Command>>,
         wasm: Option<WithMode<String>>,
         candid: Option<WithMode<String>>,
+        dependencies: Option<Vec<String>>,
+        inputs: Option<Vec<String>>,
+        compress: Option<bool>,
+        optimize: Option<WithMode<Optimize>>,
+        hooks: Option<Hooks>,
+        /// Either a list of controllers for every network, or a list per network.
+        controllers: Option<WithMode<Vec<String>>>,
+    }
+
+    /// A script, either only the commands or the commands with the settings of the script.
+    #[derive(Serialize, Deserialize, Debug)]
+    #[serde(untagged)]
+    pub enum ScriptInfo {
+        Command(Command),
+        Task(TaskInfo),
+    }
+
+    /// The commands of a script along with its settings.
+    #[derive(Serialize, Deserialize, Debug)]
+    #[serde(deny_unknown_fields)]
+    pub struct TaskInfo {
+        run: Command,
+        dependencies: Option<Vec<String>>,
+        network: Option<String>,
+        identity: Option<String>,
+    }
+
+    /// The type of a canister.
+    #[derive(Serialize, Deserialize, Debug)]
+    #[serde(rename_all = "lowercase")]
+    pub enum CanisterType {
+        Custom,
+        Rust,
+        Motoko,
+        Assets,
+        Remote,
+    }
+
+    /// The optimization of the wasm, either a simple on/off switch or the settings.
+    #[derive(Serialize, Deserialize, Debug)]
+    #[serde(untagged)]
+    pub enum Optimize {
+        Enabled(bool),
+        Settings(OptimizeInfo),
+    }
+
+    /// The settings of the optimization.
+    #[derive(Serialize, Deserialize, Debug)]
+    #[serde(deny_unknown_fields)]
+    pub struct OptimizeInfo {
+        passes: Option<Vec<String>>,
+        level: Option<u32>,
+    }
+
+    /// The hooks that are executed during the deploy of a canister.
+    #[derive(Serialize, Deserialize, Debug)]
+    #[serde(deny_unknown_fields)]
+    pub struct Hooks {
+        pre_build: Option<Command>,
+        post_build: Option<Command>,
+        pre_install: Option<Command>,
+        post_install: Option<Command>,
     }
 
     /// A type wrapper that is used for setting mode depended values
@@ -147,11 +1139,92 @@ mod manifest {
 
     impl From<CanisterInfo> for Canister {
         fn from(info: CanisterInfo) -> Self {
+            let kind = match info.kind {
+                Some(CanisterType::Remote) => CanisterKind::Remote {
+                    ids: info.remote.unwrap_or_default(),
+                },
+                Some(CanisterType::Rust) => CanisterKind::Rust {
+                    package: info.package.unwrap_or_default(),
+                },
+                Some(CanisterType::Motoko) => CanisterKind::Motoko {
+                    main: info.main.unwrap_or_default(),
+                },
+                Some(CanisterType::Assets) => CanisterKind::Assets {
+                    source: info.source.unwrap_or_default(),
+                },
+                _ => CanisterKind::Custom,
+            };
+
+            // Rust canisters are optimized by default. An empty object is parsed as a map of
+            // modes, but it stands for the default settings.
+            let optimize = match info.optimize {
+                Some(WithMode::Mode(modes)) if modes.is_empty() => {
+                    let mut map = BTreeMap::new();
+                    map.insert("default".into(), Some(OptimizeSettings::default()));
+                    map
+                }
+                Some(optimize) => optimize.into(),
+                None if matches!(kind, CanisterKind::Rust { .. }) => {
+                    let mut map = BTreeMap::new();
+                    map.insert("default".into(), Some(OptimizeSettings::default()));
+                    map
+                }
+                None => BTreeMap::new(),
+            };
+
             Self {
+                kind,
                 build: info.build.map(|x| x.into()).unwrap_or_default(),
                 test: info.test.map(|x| x.into()).unwrap_or_default(),
                 wasm: info.wasm.map(|x| x.into()).unwrap_or_default(),
                 candid: info.candid.map(|x| x.into()).unwrap_or_default(),
+                dependencies: info.dependencies.unwrap_or_default(),
+                inputs: info.inputs.unwrap_or_default(),
+                compress: info.compress.unwrap_or(false),
+                optimize,
+                hooks: info.hooks.map(|x| x.into()).unwrap_or_default(),
+                controllers: info.controllers.map(|x| x.into()).unwrap_or_default(),
+            }
+        }
+    }
+
+    impl From<Optimize> for Option<OptimizeSettings> {
+        fn from(optimize: Optimize) -> Self {
+            match optimize {
+                Optimize::Enabled(true) => Some(OptimizeSettings::default()),
+                Optimize::Enabled(false) => None,
+                Optimize::Settings(info) => Some(OptimizeSettings {
+                    passes: info.passes,
+                    level: info.level.unwrap_or(wasm_optimizer::DEFAULT_LEVEL),
+                }),
+            }
+        }
+    }
+
+    impl From<ScriptInfo> for Script {
+        fn from(info: ScriptInfo) -> Self {
+            match info {
+                ScriptInfo::Command(command) => Self {
+                    commands: command.into(),
+                    ..Default::default()
+                },
+                ScriptInfo::Task(task) => Self {
+                    commands: task.run.into(),
+                    dependencies: task.dependencies.unwrap_or_default(),
+                    network: task.network,
+                    identity: task.identity,
+                },
+            }
+        }
+    }
+
+    impl From<Hooks> for CanisterHooks {
+        fn from(hooks: Hooks) -> Self {
+            Self {
+                pre_build: hooks.pre_build.map(|x| x.into()).unwrap_or_default(),
+                post_build: hooks.post_build.map(|x| x.into()).unwrap_or_default(),
+                pre_install: hooks.pre_install.map(|x| x.into()).unwrap_or_default(),
+                post_install: hooks.post_install.map(|x| x.into()).unwrap_or_default(),
             }
         }
     }
@@ -256,4 +1329,680 @@ mod tests {
 
         serde_json::from_value::<manifest::Manifest>(manifest).expect("Failed to deserialize.");
     }
+
+    #[test]
+    fn topological_order() {
+        let manifest = serde_json::json!({
+            "canisters": {
+                "frontend": {
+                    "dependencies": ["backend", "ledger"]
+                },
+                "backend": {
+                    "dependencies": ["ledger"]
+                },
+                "ledger": {}
+            }
+        });
+
+        let workspace = Workspace::from_reader(PathBuf::new(), manifest.to_string().as_bytes())
+            .expect("Failed to load workspace.");
+        let names = workspace.canisters.keys().cloned().collect::<Vec<_>>();
+
+        assert_eq!(
+            workspace.topological_levels(&names).unwrap(),
+            vec![
+                vec!["ledger".to_string()],
+                vec!["backend".to_string()],
+                vec!["frontend".to_string()]
+            ]
+        );
+
+        assert_eq!(
+            workspace
+                .topological_order(&["frontend".to_string(), "ledger".to_string()])
+                .unwrap(),
+            vec!["ledger".to_string(), "frontend".to_string()]
+        );
+    }
+
+    #[test]
+    fn topological_order_cycle() {
+        let manifest = serde_json::json!({
+            "canisters": {
+                "a": { "dependencies": ["b"] },
+                "b": { "dependencies": ["a"] }
+            }
+        });
+
+        let workspace = Workspace::from_reader(PathBuf::new(), manifest.to_string().as_bytes())
+            .expect("Failed to load workspace.");
+
+        assert!(workspace.topological_order(&["a".to_string()]).is_err());
+    }
+
+    #[test]
+    fn manifest_remote() {
+        let manifest = serde_json::json!({
+            "canisters": {
+                "ledger": {
+                    "type": "remote",
+                    "remote": {
+                        "ic": "ryjl3-tyaaa-aaaaa-aaaba-cai"
+                    },
+                    "candid": "ledger.did",
+                    "wasm": "ledger.wasm"
+                }
+            }
+        });
+
+        let workspace = Workspace::from_reader(PathBuf::new(), manifest.to_string().as_bytes())
+            .expect("Failed to load workspace.");
+        let ledger = workspace.get_canister("ledger").unwrap();
+
+        let ic = workspace.networks.get("ic").unwrap();
+        let local = workspace.networks.get("local").unwrap();
+        let devnet = Network {
+            url: Some("http://127.0.0.1:9000".to_owned()),
+            ..local.clone()
+        };
+
+        assert!(ledger.is_remote_on("ic", ic));
+        assert!(ledger.remote_id("ic").is_some());
+        assert!(!ledger.is_remote_on("local", local));
+        assert!(!ledger.is_remote_on("devnet", &devnet));
+        assert!(ledger.is_remote_on("staging", &Network::from_url("https://example.com")));
+
+        // The stand-in wasm is prebuilt, so there is nothing to build on local networks.
+        let names = vec!["ledger".to_string()];
+        assert_eq!(
+            workspace.filter_deployable(names.clone(), "local", local),
+            names
+        );
+        assert!(workspace.filter_deployable(names, "ic", ic).is_empty());
+        assert!(ledger.build["default"].is_empty());
+        assert!(ledger.build["release"].is_empty());
+    }
+
+    #[test]
+    fn manifest_controllers() {
+        let manifest = serde_json::json!({
+            "canisters": {
+                "a": { "controllers": ["default"] },
+                "b": {
+                    "controllers": {
+                        "ic": ["ryjl3-tyaaa-aaaaa-aaaba-cai"],
+                        "default": ["default", "alice"]
+                    }
+                },
+                "c": {}
+            }
+        });
+
+        let workspace = Workspace::from_reader(PathBuf::new(), manifest.to_string().as_bytes())
+            .expect("Failed to load workspace.");
+
+        let a = workspace.get_canister("a").unwrap();
+        assert_eq!(a.controllers("ic").unwrap().len(), 1);
+
+        let b = workspace.get_canister("b").unwrap();
+        assert_eq!(
+            b.controllers("ic").unwrap(),
+            ["ryjl3-tyaaa-aaaaa-aaaba-cai"]
+        );
+        assert_eq!(b.controllers("local").unwrap(), ["default", "alice"]);
+
+        assert!(workspace
+            .get_canister("c")
+            .unwrap()
+            .controllers("ic")
+            .is_none());
+    }
+
+    #[test]
+    fn manifest_rust() {
+        let manifest = serde_json::json!({
+            "canisters": {
+                "backend": {
+                    "type": "rust",
+                    "package": "my-backend",
+                    "wasm": "backend.wasm"
+                }
+            }
+        });
+
+        let workspace = Workspace::from_reader(PathBuf::new(), manifest.to_string().as_bytes())
+            .expect("Failed to load workspace.");
+        let backend = workspace.get_canister("backend").unwrap();
+
+        assert_eq!(
+            backend.build["default"],
+            vec!["cargo build --target wasm32-unknown-unknown --package my-backend --release"]
+        );
+        assert_eq!(
+            backend.build["debug"],
+            vec!["cargo build --target wasm32-unknown-unknown --package my-backend"]
+        );
+        assert_eq!(
+            workspace.get_wasm_path("backend", "default").unwrap(),
+            PathBuf::from("backend.opt.wasm")
+        );
+    }
+
+    #[test]
+    fn manifest_interpolation() {
+        let mut dotenv = BTreeMap::new();
+        dotenv.insert("TEST_OUT_DIR".to_string(), "out".to_string());
+
+        let mut manifest = serde_json::json!({
+            "canisters": {
+                "a": {
+                    "build": "cp a.wasm ${TEST_OUT_DIR}/ && echo ${SLY_CANISTER_NAME}",
+                    "wasm": "${TEST_OUT_DIR}/a.wasm"
+                }
+            }
+        });
+
+        interpolate_value(&mut manifest, &dotenv);
+        assert_eq!(manifest["canisters"]["a"]["wasm"], "out/a.wasm");
+        assert_eq!(
+            manifest["canisters"]["a"]["build"],
+            "cp a.wasm out/ && echo ${SLY_CANISTER_NAME}"
+        );
+
+        let manifest = serde_json::json!({
+            "canisters": {
+                "a": { "wasm": "${TEST_UNDEFINED_VAR}/a.wasm" }
+            }
+        });
+
+        assert!(Workspace::from_reader(PathBuf::new(), manifest.to_string().as_bytes()).is_err());
+
+        // The variables of the commands are not available to the paths, even if sly itself
+        // runs as one of these commands.
+        dotenv.insert("SLY_MODE".to_string(), "release".to_string());
+        let mut manifest = serde_json::json!({
+            "canisters": { "a": { "build": "make ${SLY_MODE}", "wasm": "${SLY_MODE}/a.wasm" } }
+        });
+        interpolate_value(&mut manifest, &dotenv);
+        assert_eq!(manifest["canisters"]["a"]["build"], "make ${SLY_MODE}");
+
+        let error = Workspace::from_reader(PathBuf::new(), manifest.to_string().as_bytes())
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(error.contains("only set for the commands"), "{}", error);
+    }
+
+    #[test]
+    fn manifest_motoko() {
+        let manifest = serde_json::json!({
+            "canisters": {
+                "counter": {
+                    "type": "motoko",
+                    "main": "src/main.mo"
+                }
+            },
+            "packages": {
+                "base": "vendor/base"
+            }
+        });
+
+        let workspace = Workspace::from_reader(PathBuf::new(), manifest.to_string().as_bytes())
+            .expect("Failed to load workspace.");
+        let counter = workspace.get_canister("counter").unwrap();
+
+        assert_eq!(
+            counter.wasm["default"],
+            ".sly/build/counter/default/counter.wasm"
+        );
+        assert_eq!(
+            workspace.get_candid_path("counter", "debug").unwrap(),
+            PathBuf::from(".sly/build/counter/debug/counter.did")
+        );
+        assert!(counter.build["release"]
+            .iter()
+            .any(|c| c.contains("src/main.mo --package base vendor/base --release -c")));
+    }
+
+    #[test]
+    fn manifest_optimize() {
+        let manifest = serde_json::json!({
+            "canisters": {
+                "a": {
+                    "wasm": "a.wasm",
+                    "optimize": {
+                        "release": { "passes": ["binaryen"], "level": 3 },
+                        "debug": false
+                    }
+                },
+                "b": {
+                    "type": "rust",
+                    "package": "b",
+                    "wasm": "b.wasm",
+                    "optimize": false
+                },
+                "c": {
+                    "wasm": "c.wasm",
+                    "optimize": {}
+                }
+            }
+        });
+
+        let workspace = Workspace::from_reader(PathBuf::new(), manifest.to_string().as_bytes())
+            .expect("Failed to load workspace.");
+
+        let a = workspace.get_canister("a").unwrap();
+        assert_eq!(a.optimize_settings("release").unwrap().level, 3);
+        assert!(a.optimize_settings("debug").is_none());
+        assert_eq!(
+            workspace.get_wasm_path("a", "release").unwrap(),
+            PathBuf::from("a.opt.wasm")
+        );
+        assert_eq!(
+            workspace.get_wasm_path("a", "debug").unwrap(),
+            PathBuf::from("a.wasm")
+        );
+
+        assert!(workspace
+            .get_canister("b")
+            .unwrap()
+            .optimize_settings("default")
+            .is_none());
+
+        let c = workspace.get_canister("c").unwrap();
+        for mode in &["default", "release", "debug"] {
+            assert_eq!(
+                c.optimize_settings(mode).unwrap().level,
+                wasm_optimizer::DEFAULT_LEVEL
+            );
+        }
+    }
+
+    #[test]
+    fn manifest_scripts() {
+        let manifest = serde_json::json!({
+            "scripts": {
+                "bindings": "generate-bindings",
+                "seed": {
+                    "run": ["seed users", "seed tokens"],
+                    "dependencies": ["bindings", "reset"],
+                    "network": "ic",
+                    "identity": "admin"
+                },
+                "reset": {
+                    "run": "reset",
+                    "dependencies": ["bindings"]
+                },
+                "loop": {
+                    "run": "loop",
+                    "dependencies": ["loop"]
+                }
+            }
+        });
+
+        let workspace = Workspace::from_reader(PathBuf::new(), manifest.to_string().as_bytes())
+            .expect("Failed to load workspace.");
+
+        let seed = &workspace.scripts["seed"];
+        assert_eq!(seed.commands, vec!["seed users", "seed tokens"]);
+        assert_eq!(seed.network.as_deref(), Some("ic"));
+        assert_eq!(seed.identity.as_deref(), Some("admin"));
+        assert_eq!(
+            workspace.scripts["bindings"].commands,
+            vec!["generate-bindings"]
+        );
+
+        assert_eq!(
+            workspace.script_order("seed").unwrap(),
+            vec!["bindings", "reset", "seed"]
+        );
+        assert!(workspace.script_order("loop").is_err());
+        assert!(workspace.script_order("missing").is_err());
+    }
+
+    #[test]
+    fn manifest_networks() {
+        use crate::lib::network::{NetworkKind, RootKeyPolicy};
+
+        let manifest = serde_json::json!({
+            "networks": {
+                "staging": {
+                    "url": "https://staging.example.com",
+                    "wallet": "aanaa-xaaaa-aaaah-aaeiq-cai"
+                },
+                "devnet": {
+                    "url": "http://localhost:9000",
+                    "type": "ephemeral"
+                },
+                "ic": {
+                    "wallet": "ryjl3-tyaaa-aaaaa-aaaba-cai"
+                }
+            }
+        });
+
+        let workspace = Workspace::from_reader(PathBuf::new(), manifest.to_string().as_bytes())
+            .expect("Failed to load workspace.");
+
+        let staging = &workspace.networks["staging"];
+        assert_eq!(staging.url.as_deref(), Some("https://staging.example.com"));
+        assert_eq!(staging.kind, NetworkKind::Persistent);
+        assert_eq!(staging.root_key, RootKeyPolicy::Fetch);
+        assert!(staging.wallet.is_some());
+
+        assert!(workspace.networks["devnet"].is_ephemeral());
+        assert!(workspace.networks["local"].is_ephemeral());
+
+        let ic = &workspace.networks["ic"];
+        assert_eq!(ic.root_key, RootKeyPolicy::Builtin);
+        assert_eq!(
+            ic.wallet,
+            Some(Principal::from_text("ryjl3-tyaaa-aaaaa-aaaba-cai").unwrap())
+        );
+
+        let manifest = serde_json::json!({ "networks": { "staging": {} } });
+        assert!(Workspace::from_reader(PathBuf::new(), manifest.to_string().as_bytes()).is_err());
+    }
+
+    #[test]
+    fn manifest_unknown_field() {
+        let source = r#"{
+  "canisters": {
+    "cap": {
+      "biuld": "command",
+      "wasm": "cap.wasm"
+    }
+  }
+}"#;
+
+        let error = Workspace::from_reader(PathBuf::new(), source.as_bytes())
+            .err()
+            .expect("Unknown fields should be rejected.");
+        let error = error
+            .downcast_ref::<ManifestError>()
+            .expect("Expected a manifest error.");
+        let message = error.to_string();
+
+        assert!(message.contains("Unknown field 'biuld'."));
+        assert!(message.contains("sly.json:4:7"));
+    }
+
+    #[test]
+    fn manifest_invalid_value() {
+        let source = r#"{ "canisters": { "cap": { "build": 42 } } }"#;
+
+        let error = Workspace::from_reader(PathBuf::new(), source.as_bytes())
+            .err()
+            .unwrap();
+        let message = error.downcast_ref::<ManifestError>().unwrap().to_string();
+        assert!(message.contains("Invalid value for 'build'."));
+
+        let source = r#"{ "canisters": { "#;
+        let error = Workspace::from_reader(PathBuf::new(), source.as_bytes())
+            .err()
+            .unwrap();
+        assert!(error.downcast_ref::<ManifestError>().is_some());
+    }
+
+    #[test]
+    fn manifest_schema() {
+        let schema = serde_json::from_str::<serde_json::Value>(MANIFEST_SCHEMA)
+            .expect("The schema should be valid json.");
+        let manifest =
+            serde_json::to_value(serde_json::from_str::<manifest::Manifest>("{}").unwrap())
+                .unwrap();
+
+        let properties = schema["properties"].as_object().unwrap();
+        for key in manifest.as_object().unwrap().keys() {
+            assert!(
+                properties.contains_key(key),
+                "'{}' is not in the schema.",
+                key
+            );
+        }
+
+        // The fields of the nested objects must match the schema exactly.
+        fn keys<T: Serialize>(value: T) -> BTreeSet<String> {
+            let value = serde_json::to_value(value).unwrap();
+            value.as_object().unwrap().keys().cloned().collect()
+        }
+
+        fn schema_keys(schema: &serde_json::Value) -> BTreeSet<String> {
+            schema["properties"]
+                .as_object()
+                .unwrap()
+                .keys()
+                .cloned()
+                .collect()
+        }
+
+        let definitions = &schema["definitions"];
+        let canister = serde_json::from_str::<manifest::CanisterInfo>("{}").unwrap();
+        assert_eq!(keys(canister), schema_keys(&definitions["canister"]));
+
+        let network = serde_json::from_str::<manifest::NetworkInfo>("{}").unwrap();
+        assert_eq!(keys(network), schema_keys(&definitions["network"]));
+
+        let task = serde_json::from_str::<manifest::TaskInfo>(r#"{ "run": "" }"#).unwrap();
+        assert_eq!(keys(task), schema_keys(&definitions["script"]["oneOf"][1]));
+
+        let hooks = serde_json::from_str::<manifest::Hooks>("{}").unwrap();
+        assert_eq!(
+            keys(hooks),
+            schema_keys(&definitions["canister"]["properties"]["hooks"])
+        );
+
+        let optimize = serde_json::from_str::<manifest::OptimizeInfo>("{}").unwrap();
+        assert_eq!(
+            keys(optimize),
+            schema_keys(&definitions["optimize"]["oneOf"][1])
+        );
+    }
+
+    #[test]
+    fn manifest_untagged_unknown_field() {
+        let message = |source: &str| {
+            let error = Workspace::from_reader(PathBuf::new(), source.as_bytes())
+                .err()
+                .unwrap();
+            error.downcast_ref::<ManifestError>().unwrap().to_string()
+        };
+
+        let source =
+            r#"{ "canisters": { "a": { "wasm": "a.wasm", "optimize": { "levle": 3 } } } }"#;
+        let error = message(source);
+        assert!(error.contains("Unknown field 'levle'."), "{}", error);
+
+        let source = r#"{
+  "canisters": {
+    "a": { "wasm": "a.wasm", "optimize": { "release": { "pases": [] } } }
+  }
+}"#;
+        let error = message(source);
+        assert!(error.contains("Unknown field 'pases'."), "{}", error);
+        assert!(error.contains("sly.json:3:57"), "{}", error);
+
+        let source = r#"{ "scripts": { "dev": { "runn": "npm start" } } }"#;
+        let error = message(source);
+        assert!(error.contains("Unknown field 'runn'."), "{}", error);
+    }
+
+    #[test]
+    fn manifest_profiles() {
+        let manifest = serde_json::json!({
+            "canisters": {
+                "a": {
+                    "build": { "default": "build", "release": "build --release" },
+                    "wasm": "a.wasm",
+                    "controllers": ["alice"]
+                }
+            },
+            "profiles": {
+                "prod": {
+                    "canisters": {
+                        "a": {
+                            "build": { "release": "build --release --locked" },
+                            "controllers": ["bob", "carol"]
+                        }
+                    }
+                }
+            }
+        });
+        let source = manifest.to_string();
+
+        let workspace = Workspace::from_reader(PathBuf::new(), source.as_bytes()).unwrap();
+        let a = workspace.get_canister("a").unwrap();
+        assert_eq!(a.build["release"], vec!["build --release"]);
+        assert_eq!(a.controllers("ic").unwrap(), ["alice"]);
+        assert!(workspace.manifest.get("profiles").is_none());
+
+        let workspace =
+            Workspace::from_reader_with_profile(PathBuf::new(), source.as_bytes(), Some("prod"))
+                .unwrap();
+        let a = workspace.get_canister("a").unwrap();
+        assert_eq!(a.build["default"], vec!["build"]);
+        assert_eq!(a.build["release"], vec!["build --release --locked"]);
+        assert_eq!(a.controllers("ic").unwrap(), ["bob", "carol"]);
+
+        assert!(Workspace::from_reader_with_profile(
+            PathBuf::new(),
+            source.as_bytes(),
+            Some("dev")
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn manifest_profile_modes() {
+        let manifest = serde_json::json!({
+            "canisters": {
+                "a": {
+                    "build": "build",
+                    "wasm": "a.wasm",
+                    "optimize": { "level": 2 }
+                }
+            },
+            "profiles": {
+                "prod": {
+                    "canisters": {
+                        "a": {
+                            "build": { "release": "build --release" },
+                            "optimize": { "release": { "level": 4 } }
+                        }
+                    }
+                },
+                "fast": {
+                    "canisters": { "a": { "optimize": { "level": 1 } } }
+                }
+            }
+        });
+        let source = manifest.to_string();
+
+        let workspace =
+            Workspace::from_reader_with_profile(PathBuf::new(), source.as_bytes(), Some("prod"))
+                .unwrap();
+        let a = workspace.get_canister("a").unwrap();
+        assert_eq!(a.build["default"], vec!["build"]);
+        assert_eq!(a.build["release"], vec!["build --release"]);
+        assert_eq!(a.optimize_settings("debug").unwrap().level, 2);
+        assert_eq!(a.optimize_settings("release").unwrap().level, 4);
+
+        let workspace =
+            Workspace::from_reader_with_profile(PathBuf::new(), source.as_bytes(), Some("fast"))
+                .unwrap();
+        let a = workspace.get_canister("a").unwrap();
+        assert_eq!(a.optimize_settings("default").unwrap().level, 1);
+    }
+
+    #[test]
+    fn manifest_keeps_variables() {
+        let dir = env::temp_dir().join(format!("sly-variables-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(".env"), "TEST_DEPLOY_SECRET=secret\n").unwrap();
+
+        let manifest = serde_json::json!({
+            "canisters": { "a": { "build": "deploy ${TEST_DEPLOY_SECRET}", "wasm": "a.wasm" } }
+        });
+
+        let workspace = Workspace::from_reader(dir.clone(), manifest.to_string().as_bytes())
+            .expect("Failed to load workspace.");
+        assert_eq!(
+            workspace.get_canister("a").unwrap().build["default"],
+            vec!["deploy secret"]
+        );
+        assert_eq!(workspace.manifest, manifest);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn manifest_extends() {
+        let dir = env::temp_dir().join(format!("sly-extends-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("shared")).unwrap();
+
+        let base = serde_json::json!({
+            "canisters": {
+                "a": { "build": "build a", "wasm": "a.wasm" },
+                "b": { "build": "build b", "wasm": "b.wasm" }
+            },
+            "profiles": { "prod": { "canisters": { "b": { "wasm": "b.prod.wasm" } } } }
+        });
+        std::fs::write(dir.join("shared/base.json"), base.to_string()).unwrap();
+
+        let manifest = serde_json::json!({
+            "extends": "shared/base.json",
+            "canisters": { "a": { "build": "build a --fast" } }
+        });
+
+        let workspace = Workspace::from_reader_with_profile(
+            dir.clone(),
+            manifest.to_string().as_bytes(),
+            Some("prod"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            workspace.get_canister("a").unwrap().build["default"],
+            vec!["build a --fast"]
+        );
+        assert_eq!(
+            workspace.get_canister("b").unwrap().wasm["default"],
+            "b.prod.wasm"
+        );
+
+        // The errors of the values that come from the base point at the base.
+        let base = serde_json::json!({
+            "canisters": {
+                "a": { "build": 42, "wasm": "a.wasm" },
+                "b": { "type": "rust" }
+            }
+        });
+        std::fs::write(dir.join("shared/base.json"), base.to_string()).unwrap();
+        let manifest = serde_json::json!({
+            "extends": "shared/base.json",
+            "canisters": { "c": { "build": "build c", "wasm": "c.wasm" } }
+        });
+        let error = Workspace::from_reader(dir.clone(), manifest.to_string().as_bytes())
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(error.contains("base.json:1:"), "{}", error);
+        assert!(error.contains("42"), "{}", error);
+
+        let base = serde_json::json!({ "canisters": { "b": { "type": "rust" } } });
+        std::fs::write(dir.join("shared/base.json"), base.to_string()).unwrap();
+        let error = Workspace::from_reader(dir.clone(), manifest.to_string().as_bytes())
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(error.contains("base.json:1:"), "{}", error);
+        assert!(error.contains("does not specify a package"), "{}", error);
+
+        // A manifest that extends itself.
+        let cyclic = serde_json::json!({ "extends": "base.json" });
+        std::fs::write(dir.join("shared/base.json"), cyclic.to_string()).unwrap();
+        let manifest = serde_json::json!({ "extends": "shared/base.json" });
+        assert!(Workspace::from_reader(dir.clone(), manifest.to_string().as_bytes()).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -5,7 +5,7 @@ use log::LevelFilter;
 use pretty_env_logger::formatted_builder;
 
 use crate::lib::command::Command;
-use crate::lib::env::Env;
+use crate::lib::env::{inherited_var, Env};
 
 mod actors;
 mod commands;
@@ -27,9 +27,13 @@ fn main() -> Result<()> {
 
     formatted_builder().filter(None, level).init();
 
-    let identity = opts.identity.as_deref();
+    // Commands executed by sly inherit the network, the identity and the profile of the
+    // parent process.
+    let network = opts.network.or_else(|| inherited_var("SLY_NETWORK"));
+    let identity = opts.identity.or_else(|| inherited_var("SLY_IDENTITY"));
+    let profile = opts.profile.or_else(|| inherited_var("SLY_PROFILE"));
     let config_path = opts.config;
-    let mut env = Env::new(opts.network, identity, config_path)?;
+    let mut env = Env::new(network, identity.as_deref(), profile, config_path)?;
 
     opts.sub.exec(&mut env)
 }
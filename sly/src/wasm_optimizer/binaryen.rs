@@ -3,7 +3,10 @@ use binaryen::{CodegenConfig, Module};
 
 use super::OptimizationPass;
 
-pub struct BinaryenPass {}
+pub struct BinaryenPass {
+    /// The binaryen optimization level.
+    pub level: u32,
+}
 
 impl OptimizationPass for BinaryenPass {
     fn short_name(&self) -> String {
@@ -19,7 +22,7 @@ impl OptimizationPass for BinaryenPass {
 
         module.optimize(&CodegenConfig {
             debug_info: false,
-            optimization_level: 2,
+            optimization_level: self.level,
             shrink_level: 2,
         });
 
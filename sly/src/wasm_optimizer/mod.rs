@@ -1,8 +1,15 @@
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
+use humansize::{file_size_opts, FileSize};
 
 mod binaryen;
 mod strip_data;
 
+/// The binaryen optimization level that is used by default.
+pub const DEFAULT_LEVEL: u32 = 2;
+
+/// The highest optimization level supported by binaryen.
+pub const MAX_LEVEL: u32 = 4;
+
 pub trait OptimizationPass {
     fn short_name(&self) -> String;
     fn description(&self) -> String;
@@ -11,8 +18,82 @@ pub trait OptimizationPass {
 
 /// Return a vector of optimization passes.
 pub fn create() -> Vec<Box<dyn OptimizationPass>> {
+    create_with_level(DEFAULT_LEVEL)
+}
+
+/// Return a vector of optimization passes, using the given binaryen optimization level.
+pub fn create_with_level(level: u32) -> Vec<Box<dyn OptimizationPass>> {
     vec![
         Box::new(strip_data::RemoveDebugSymbolsPass {}),
-        Box::new(binaryen::BinaryenPass {}),
+        Box::new(binaryen::BinaryenPass { level }),
     ]
 }
+
+/// Return the passes with the given names in the given order, or all of the passes if no
+/// names are provided.
+pub fn create_from_names(
+    names: Option<&[String]>,
+    level: u32,
+) -> Result<Vec<Box<dyn OptimizationPass>>> {
+    if level > MAX_LEVEL {
+        bail!(
+            "Invalid binaryen optimization level {}, expected a value between 0 and {}.",
+            level,
+            MAX_LEVEL
+        );
+    }
+
+    let mut passes = create_with_level(level);
+
+    let names = match names {
+        Some(names) => names,
+        None => return Ok(passes),
+    };
+
+    let mut result = Vec::with_capacity(names.len());
+    for name in names {
+        match passes.iter().position(|p| &p.short_name() == name) {
+            Some(index) => result.push(passes.remove(index)),
+            None => bail!(
+                "Unknown optimization pass '{}', expected one of: {}",
+                name,
+                create()
+                    .iter()
+                    .map(|p| p.short_name())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+
+    Ok(result)
+}
+
+/// Run the given passes on the module and log the size savings of each pass.
+pub fn run(passes: Vec<Box<dyn OptimizationPass>>, wasm: &[u8]) -> Result<Vec<u8>> {
+    let mut wasm = wasm.to_vec();
+    let original_size = wasm.len();
+
+    for pass in passes {
+        log::info!("Running optimization pass {}", pass.short_name());
+
+        let original_size = wasm.len();
+        wasm = pass.opt(&wasm).context("Optimization pass failed.")?;
+        let size = wasm.len();
+
+        log::info!(
+            "    Size:          {:>8} ({:3.1}% smaller)",
+            size.file_size(file_size_opts::BINARY).unwrap(),
+            (1.0 - ((size as f64) / (original_size as f64))) * 100.0
+        );
+    }
+
+    let size = wasm.len();
+    log::info!(
+        "Final Size:        {:>8} ({:3.1}% smaller)",
+        size.file_size(file_size_opts::BINARY).unwrap(),
+        (1.0 - ((size as f64) / (original_size as f64))) * 100.0
+    );
+
+    Ok(wasm)
+}